@@ -1,16 +1,16 @@
-use crate::ais::msg::{get_text_content, user_msg};
+use crate::ais::msg::get_text_content;
 use crate::ais::OaClient;
-use crate::utils::cli::{
-    ico_check, ico_deleted_ok, ico_err, ico_uploaded, ico_uploading,
-};
+use crate::ais::steps;
+use crate::utils::cli::{ico_check, ico_deleted_ok, ico_err, ico_info, new_spinner};
 use crate::utils::files::XFile;
+use crate::utils::interrupt::RunInterrupts;
 use crate::Result;
 use async_openai::types::{
-    AssistantObject, AssistantToolsRetrieval, CreateAssistantFileRequest,
-    CreateAssistantRequest, CreateFileRequest, CreateRunRequest,
-    CreateThreadRequest, ModifyAssistantRequest, RunStatus, ThreadObject,
+    AssistantObject, AssistantTools, AssistantToolsCode, AssistantToolsRetrieval, CreateAssistantFileRequest,
+    CreateAssistantRequest, CreateFileRequest, CreateMessageRequest, CreateRunRequest,
+    CreateThreadRequest, MessageContent, MessageRole, ModifyAssistantRequest, RunCompletionUsage, RunObject, RunStatus,
+    ThreadObject,
 };
-use console::Term;
 use derive_more::{Deref, Display, From};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -21,17 +21,80 @@ use tokio::time::sleep;
 #[ allow(unused)]
 const DEFAULT_QUERY: &[(&str, &str)] = &[("limit", "100")];
 const POLLING_DURATION_MS: u64 = 500;
+/// Max page size of the list endpoints.
+const LIST_PAGE_SIZE: usize = 100;
+/// Starts the answers copied to a new thread (see `copy_thread`), where they are user messages.
+const COPIED_ANSWER_PREFIX: &str = "[Your earlier answer in this conversation]";
 pub struct CreateConfig {
     pub(crate) name: String,
     pub(crate) model: String,
 }
 
-#[derive(Debug, From, Deref, Display)]
+#[derive(Debug, Clone, From, Deref, Display)]
 pub struct AssistantId(String);
-#[derive(Debug, From, Deref, Display, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, From, Deref, Display, Serialize, Deserialize)]
 pub struct ThreadId(String);
-#[derive(Debug, From, Deref, Display)]
+#[derive(Debug, Clone, From, Deref, Display)]
 pub struct FileId(String);
+
+/// Run-level overrides of the assistant settings, for one run.
+/// `None` keeps the setting of the assistant.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunOverrides {
+    pub model: Option<String>,
+    /// Replace the instructions of the assistant.
+    pub instructions: Option<String>,
+    /// Appended to the instructions of the assistant.
+    pub additional_instructions: Option<String>,
+    /// `Some(vec![])` for no tools.
+    pub tools: Option<Vec<RunTool>>,
+}
+
+impl RunOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `self` with the settings of `other` on top.
+    pub fn merged(&self, other: &RunOverrides) -> RunOverrides {
+        RunOverrides {
+            model: other.model.clone().or_else(|| self.model.clone()),
+            instructions: other.instructions.clone().or_else(|| self.instructions.clone()),
+            additional_instructions: other
+                .additional_instructions
+                .clone()
+                .or_else(|| self.additional_instructions.clone()),
+            tools: other.tools.clone().or_else(|| self.tools.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum RunTool {
+    #[display("retrieval")]
+    Retrieval,
+    #[display("code_interpreter")]
+    CodeInterpreter,
+}
+
+impl RunTool {
+    fn into_tool(self) -> AssistantTools {
+        match self {
+            RunTool::Retrieval => AssistantToolsRetrieval::default().into(),
+            RunTool::CodeInterpreter => AssistantToolsCode::default().into(),
+        }
+    }
+}
+
+/// Outcome of a completed run: the answer and what it cost.
+#[derive(Debug)]
+pub struct RunRes {
+    pub run_id: String,
+    pub text: String,
+    pub model: String,
+    pub usage: Option<RunCompletionUsage>,
+}
+
 pub async fn create(open_ai_client: &OaClient, config: CreateConfig) -> Result<AssistantId> {
     let open_ai_assistant = open_ai_client.assistants();
 
@@ -97,18 +160,29 @@ pub async fn upload_instructions(
         instructions: Some(inst_content),
         ..Default::default()
     };
-    open_ai_assistants.update(&assistant_id.0.as_str(), modif).await?;
+    open_ai_assistants.update(assistant_id.0.as_str(), modif).await?;
 
     Ok(())
 }
 
+/// The instructions the assistant currently runs with.
+pub async fn get_instructions(
+    open_ai_client: &OaClient,
+    assistant_id: &AssistantId,
+) -> Result<Option<String>> {
+    let open_ai_assistants = open_ai_client.assistants();
+    let assistant_obj = open_ai_assistants.retrieve(assistant_id.0.as_str()).await?;
+
+    Ok(assistant_obj.instructions)
+}
+
 pub async fn delete(open_ai_client: &OaClient, assistant_id: &AssistantId) -> Result<()> {
     let open_ai_assistant = open_ai_client.assistants();
 
     // TODO: Delete files
 
     // Delete assistant
-    open_ai_assistant.delete(&assistant_id.0.as_str()).await?;
+    open_ai_assistant.delete(assistant_id.0.as_str()).await?;
     Ok(())
 }
 
@@ -142,48 +216,170 @@ pub async fn run_thread_msg(
     open_ai_client: &OaClient,
     assistant_id: &AssistantId,
     thread_id: &ThreadId,
-    msg: &str,
-) -> Result<String> {
-    let msg = user_msg(msg);
+    msg: CreateMessageRequest,
+    overrides: &RunOverrides,
+    verbose: bool,
+) -> Result<RunRes> {
+    add_thread_msg(open_ai_client, thread_id, msg).await?;
+
+    run_thread(open_ai_client, assistant_id, thread_id, overrides, verbose).await
+}
 
-    // Attach message to thread
-    let _message_obj = open_ai_client.threads().messages(&thread_id.0).create(msg).await?;
+/// Adds `msg` to the thread, without running the assistant.
+pub async fn add_thread_msg(open_ai_client: &OaClient, thread_id: &ThreadId, msg: CreateMessageRequest) -> Result<()> {
+    open_ai_client.threads().messages(&thread_id.0).create(msg).await?;
+    Ok(())
+}
 
+/// Runs the assistant on the thread as it is (e.g., to retry the last answer),
+/// and waits for the answer. With `verbose`, the steps of the run are printed as they go.
+pub async fn run_thread(
+    open_ai_client: &OaClient,
+    assistant_id: &AssistantId,
+    thread_id: &ThreadId,
+    overrides: &RunOverrides,
+    verbose: bool,
+) -> Result<RunRes> {
     // Create a run for the thread
+    let overrides = overrides.clone();
     let run_request = CreateRunRequest {
         assistant_id: assistant_id.0.to_string(),
+        model: overrides.model,
+        instructions: overrides.instructions,
+        additional_instructions: overrides.additional_instructions,
+        tools: overrides.tools.map(|tools| tools.into_iter().map(RunTool::into_tool).collect()),
         ..Default::default()
     };
     let run = open_ai_client.threads().runs(&thread_id.0).create(run_request).await?;
 
-    // Loop to get result
-    let term = Term::stdout();
-    loop {
-        term.write_str(">")?;
-        let run = open_ai_client.threads().runs(&thread_id.0).retrieve(&run.id).await?;
-        term.write_str("< ")?;
+    await_run(open_ai_client, thread_id, &run.id, verbose).await
+}
+
+/// Waits for the answer of the run, or cancels the run on Ctrl-C (a second one
+/// stops waiting for the cancellation). Shows a spinner, or with `verbose` the steps of the run.
+pub async fn await_run(open_ai_client: &OaClient, thread_id: &ThreadId, run_id: &str, verbose: bool) -> Result<RunRes> {
+    let mut interrupts = RunInterrupts::start();
+    let spinner = (!verbose).then(|| new_spinner("Waiting for the answer"));
+    let clear_spinner = || {
+        if let Some(spinner) = spinner.as_ref() {
+            spinner.finish_and_clear();
+        }
+    };
+
+    tokio::select! {
+        res = wait_run(open_ai_client, thread_id, run_id, verbose) => {
+            clear_spinner();
+            res
+        }
+        _ = interrupts.next() => {
+            clear_spinner();
+            println!("{} Cancelling the run (Ctrl-C again to stop waiting)", ico_info());
+            let run = tokio::select! {
+                run = cancel_run(open_ai_client, thread_id, run_id) => run?,
+                _ = interrupts.next() => {
+                    return Err("Stopped waiting for the run to be cancelled, it is checked before the next message".into())
+                }
+            };
+            match run.status {
+                // Too late to cancel, the answer is there.
+                RunStatus::Completed => completed_run_res(open_ai_client, thread_id, run).await,
+                _ => Err("Run cancelled".into()),
+            }
+        }
+    }
+}
 
-        // Log the status of the run
-        println!("Run status: {:?}", run.status);
+/// Polls the run until it ends.
+async fn wait_run(open_ai_client: &OaClient, thread_id: &ThreadId, run_id: &str, verbose: bool) -> Result<RunRes> {
+    let mut printed_steps = HashSet::new();
+    loop {
+        let run = open_ai_client.threads().runs(&thread_id.0).retrieve(run_id).await?;
+        if verbose {
+            steps::print_new_steps(open_ai_client, thread_id, run_id, &mut printed_steps).await?;
+        }
 
         match run.status {
             RunStatus::Completed => {
-                term.write_str("\n")?;
-                return get_first_thread_msg_content(open_ai_client, thread_id).await;
+                return completed_run_res(open_ai_client, thread_id, run).await;
             }
-            RunStatus::InProgress => {
-                // If the status is InProgress, continue polling
+            RunStatus::Queued | RunStatus::InProgress => {
+                // If the run is not done yet, continue polling
                 sleep(Duration::from_millis(POLLING_DURATION_MS)).await;
                 continue;
             }
             other => {
-                term.write_str("\n")?;
-                return Err(format!("ERROR WHILE RUN: {:?}", other).into());
+                let reason = run.last_error.map(|err| format!(": {}", err.message)).unwrap_or_default();
+                return Err(format!("Run ended with status {other:?}{reason} (see /steps)").into());
             }
         };
     }
 }
 
+/// A run that has not ended yet, e.g. left by a session that crashed.
+/// A thread cannot get new messages while it has one.
+#[derive(Debug)]
+pub struct ActiveRun {
+    pub id: String,
+    pub status: RunStatus,
+    pub created_at: i64,
+}
+
+/// The active run of the thread, if any (there is at most one, the latest).
+pub async fn active_run(open_ai_client: &OaClient, thread_id: &ThreadId) -> Result<Option<ActiveRun>> {
+    let active = last_run(open_ai_client, thread_id).await?.filter(|run| {
+        matches!(
+            run.status,
+            RunStatus::Queued | RunStatus::InProgress | RunStatus::RequiresAction | RunStatus::Cancelling
+        )
+    });
+    Ok(active.map(|run| ActiveRun {
+        id: run.id,
+        status: run.status,
+        created_at: run.created_at as i64,
+    }))
+}
+
+/// The id of the last run of the thread, if any.
+pub async fn last_run_id(open_ai_client: &OaClient, thread_id: &ThreadId) -> Result<Option<String>> {
+    Ok(last_run(open_ai_client, thread_id).await?.map(|run| run.id))
+}
+
+async fn last_run(open_ai_client: &OaClient, thread_id: &ThreadId) -> Result<Option<RunObject>> {
+    let oa_threads = open_ai_client.threads();
+    let runs = oa_threads.runs(&thread_id.0).list(&[("limit", "1")]).await?;
+
+    Ok(runs.data.into_iter().next())
+}
+
+/// Cancels the run, and waits until it is no longer active.
+/// Returns the run, which may have completed (or failed) in the meantime.
+pub async fn cancel_run(open_ai_client: &OaClient, thread_id: &ThreadId, run_id: &str) -> Result<RunObject> {
+    let oa_threads = open_ai_client.threads();
+    let oa_runs = oa_threads.runs(&thread_id.0);
+
+    // Fails when the run just ended, which the status below tells.
+    let _ = oa_runs.cancel(run_id).await;
+    loop {
+        let run = oa_runs.retrieve(run_id).await?;
+        match run.status {
+            RunStatus::Queued | RunStatus::InProgress | RunStatus::Cancelling => {
+                sleep(Duration::from_millis(POLLING_DURATION_MS)).await;
+            }
+            _ => return Ok(run),
+        }
+    }
+}
+
+async fn completed_run_res(open_ai_client: &OaClient, thread_id: &ThreadId, run: RunObject) -> Result<RunRes> {
+    let text = get_first_thread_msg_content(open_ai_client, thread_id).await?;
+    Ok(RunRes {
+        run_id: run.id,
+        text,
+        model: run.model,
+        usage: run.usage,
+    })
+}
+
 pub async fn get_first_thread_msg_content(
     open_ai_client: &OaClient,
     thread_id: &ThreadId,
@@ -201,93 +397,274 @@ pub async fn get_first_thread_msg_content(
     Ok(text)
 }
 
-#[allow(dead_code)]
+/// A message of a thread, as listed by `list_thread_msgs`.
+#[derive(Debug, Clone)]
+pub struct ThreadMsg {
+    pub role: MessageRole,
+    /// Unix timestamp (in seconds).
+    pub created_at: i64,
+    pub run_id: Option<String>,
+    pub text: String,
+    /// Files attached to a user message (see `upload_msg_file`).
+    pub file_ids: Vec<String>,
+}
+
+/// All the messages of the thread, oldest first.
+pub async fn list_thread_msgs(open_ai_client: &OaClient, thread_id: &ThreadId) -> Result<Vec<ThreadMsg>> {
+    let mut msgs = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next) = list_msgs_page(open_ai_client, thread_id, "asc", 100, cursor).await?;
+        msgs.extend(page);
+        match next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(msgs)
+}
+
+/// At most `limit` messages of the thread, newest first, older than the
+/// `cursor` of a previous page (the newest ones when `None`).
+/// Returns them with the cursor of the next (older) page, if any.
+pub async fn list_thread_msgs_page(
+    open_ai_client: &OaClient,
+    thread_id: &ThreadId,
+    limit: usize,
+    cursor: Option<String>,
+) -> Result<(Vec<ThreadMsg>, Option<String>)> {
+    list_msgs_page(open_ai_client, thread_id, "desc", limit, cursor).await
+}
+
+async fn list_msgs_page(
+    open_ai_client: &OaClient,
+    thread_id: &ThreadId,
+    order: &str,
+    limit: usize,
+    after: Option<String>,
+) -> Result<(Vec<ThreadMsg>, Option<String>)> {
+    let mut query = vec![("order", order.to_string()), ("limit", limit.to_string())];
+    if let Some(after) = after {
+        query.push(("after", after));
+    }
+    let page = open_ai_client.threads().messages(&thread_id.0).list(&query).await?;
+
+    let msgs = page
+        .data
+        .into_iter()
+        .map(|msg| {
+            let texts: Vec<String> = msg
+                .content
+                .into_iter()
+                .map(|content| match content {
+                    MessageContent::Text(text) => text.text.value,
+                    MessageContent::ImageFile(image) => format!("[image {}]", image.image_file.file_id),
+                })
+                .collect();
+            let (role, text) = uncopied_msg(msg.role, texts.join("\n\n"));
+            ThreadMsg {
+                role,
+                created_at: msg.created_at as i64,
+                run_id: msg.run_id,
+                text,
+                file_ids: msg.file_ids,
+            }
+        })
+        .collect();
+    let next = page.last_id.filter(|_| page.has_more);
+
+    Ok((msgs, next))
+}
+
+/// Creates a new thread with the `msgs`, e.g. as thread messages cannot be deleted.
+/// The API (v1) only creates user messages: the answers are copied as user
+/// messages starting with `COPIED_ANSWER_PREFIX`, listed back as answers.
+/// The files of the user messages are attached to their copy.
+/// The new thread is deleted if a message cannot be copied.
+pub async fn copy_thread(open_ai_client: &OaClient, msgs: Vec<ThreadMsg>) -> Result<ThreadId> {
+    let new_thread_id = create_thread(open_ai_client).await?;
+    let oa_threads = open_ai_client.threads();
+    let oa_messages = oa_threads.messages(&new_thread_id.0);
+    for msg in msgs {
+        let file_ids = (msg.role == MessageRole::User && !msg.file_ids.is_empty()).then(|| msg.file_ids.clone());
+        let res = oa_messages
+            .create(CreateMessageRequest {
+                role: "user".to_string(),
+                content: copied_msg_text(msg),
+                file_ids,
+                metadata: None,
+            })
+            .await;
+        if let Err(err) = res {
+            if let Err(del_err) = oa_threads.delete(&new_thread_id.0).await {
+                println!("{} Can't delete the partial thread '{new_thread_id}'\n    cause: {del_err}", ico_err());
+            }
+            return Err(err.into());
+        }
+    }
+
+    Ok(new_thread_id)
+}
+
+fn copied_msg_text(msg: ThreadMsg) -> String {
+    match msg.role {
+        MessageRole::User => msg.text,
+        MessageRole::Assistant => format!("{COPIED_ANSWER_PREFIX}\n\n{}", msg.text),
+    }
+}
+
+/// The role and text of a message, as before `copy_thread`.
+fn uncopied_msg(role: MessageRole, text: String) -> (MessageRole, String) {
+    match text.strip_prefix(COPIED_ANSWER_PREFIX) {
+        Some(answer) if role == MessageRole::User => {
+            (MessageRole::Assistant, answer.trim_start_matches('\n').to_string())
+        }
+        _ => (role, text),
+    }
+}
+
+/// Uploads a local file to the account, to be attached to a message
+/// (see `CreateMessageRequest::file_ids`).
+pub async fn upload_msg_file(open_ai_client: &OaClient, file: &Path) -> Result<FileId> {
+    let oa_file = open_ai_client
+        .files()
+        .create(CreateFileRequest {
+            file: file.into(),
+            purpose: "assistants".into(),
+        })
+        .await?;
+
+    Ok(oa_file.id.into())
+}
+
+/// Deletes a file uploaded with `upload_msg_file`.
+pub async fn delete_msg_file(open_ai_client: &OaClient, file_id: &FileId) -> Result<()> {
+    open_ai_client.files().delete(&file_id.0).await?;
+    Ok(())
+}
+
+/// A file attached to an assistant.
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    pub id: FileId,
+    pub bytes: u64,
+}
+
+/// Files attached to the assistant, by file name.
 pub async fn get_files_hashmap(
     open_ai_client: &OaClient,
     assistant_id: &AssistantId,
-) -> Result<HashMap<String, FileId>> {
-    // Get all asst files (files do not have .name)
+) -> Result<HashMap<String, RemoteFile>> {
+    // Get all asst files (files do not have .name), page by page
     let oas_assts = open_ai_client.assistants();
-    let oa_asst_files = oas_assts.files(&assistant_id.0); // Dereference `assistant_id` here
-    let asst_files = oa_asst_files.list(DEFAULT_QUERY).await?.data;
-    let asst_files_ids: HashSet<String> =
-        asst_files.into_iter().map(|f| f.id).collect();
+    let oa_asst_files = oas_assts.files(&assistant_id.0);
+    let mut asst_files_ids: HashSet<String> = HashSet::new();
+    let mut after: Option<String> = None;
+    loop {
+        let page = oa_asst_files.list(&list_query(after.take())).await?;
+        asst_files_ids.extend(page.data.into_iter().map(|f| f.id));
+        match page.last_id.filter(|_| page.has_more) {
+            Some(last_id) => after = Some(last_id),
+            None => break,
+        }
+    }
 
-    // Get all files for org (those files have .filename)
+    if asst_files_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Get the org files (those files have .filename), until all the asst files are found.
+    // The org files list tells no `has_more`: a full page may have a next one.
     let oa_files = open_ai_client.files();
-    let org_files = oa_files.list(DEFAULT_QUERY).await?.data; // Check the expected argument for `list` method
+    let mut file_by_name: HashMap<String, RemoteFile> = HashMap::new();
+    let mut num_found = 0;
+    loop {
+        let org_files = oa_files.list(&list_query(after.take())).await?.data;
+        let full_page = org_files.len() >= LIST_PAGE_SIZE;
+        after = org_files.last().map(|f| f.id.clone());
+
+        for org_file in org_files.into_iter().filter(|f| asst_files_ids.contains(&f.id)) {
+            num_found += 1;
+            let remote_file = RemoteFile {
+                id: org_file.id.into(),
+                bytes: org_file.bytes as u64,
+            };
+            file_by_name.insert(org_file.filename, remote_file);
+        }
+        if !full_page || num_found >= asst_files_ids.len() {
+            break;
+        }
+    }
 
-    // Build or file_name::file_id hashmap
-    let file_id_by_name:HashMap<String, FileId> = org_files
-        .into_iter()
-        .filter(|org_file| asst_files_ids.contains(&org_file.id))
-        .map(|org_file| (org_file.filename, org_file.id.into()))
-        .collect();
+    Ok(file_by_name)
+}
 
-    Ok(file_id_by_name)
+/// Query of a list page, after the `after` id (from the start when `None`).
+fn list_query(after: Option<String>) -> Vec<(&'static str, String)> {
+    let mut query = vec![("limit", LIST_PAGE_SIZE.to_string())];
+    if let Some(after) = after {
+        query.push(("after", after));
+    }
+    query
+}
+
+/// Deletes an assistant file (the account file, then the asst association).
+/// Failures are reported but not returned, so that a sync can go on.
+pub async fn delete_file(oac: &OaClient, asst_id: &AssistantId, file_id: &FileId, file_name: &str) {
+    // -- Delete the org file
+    let oa_files = oac.files();
+    if let Err(err) = oa_files.delete(&file_id.0).await {
+        println!(
+            "{} Can't delete file '{}'\n    cause: {:?}",
+            ico_err(),
+            file_name,
+            err
+        );
+    }
+
+    // -- Delete the asst_file association
+    let oa_assts = oac.assistants();
+    let oa_assts_files = oa_assts.files(&asst_id.0);
+    if let Err(err) = oa_assts_files.delete(&file_id.0).await {
+        println!(
+            "{} Can't remove assistant file '{}'\n    cause: {:?}",
+            ico_err(),
+            file_name,
+            err
+        );
+    }
 }
 
 /// Uploads a file to an assistant (first to the account, then attaches to asst)
+/// - `file_by_name` is the remote listing (see `get_files_hashmap`), fetched
+///   once by the caller for all the files of a sync.
 /// - `force` is `false`, will not upload the file if already uploaded.
 /// - `force` is `true`, it will delete existing file (account and asst), and upload.
 ///
 /// Returns `(FileId, has_been_uploaded)`
-#[allow(dead_code)]
 pub async fn upload_file_by_name(
     oac: &OaClient,
     asst_id: &AssistantId,
     file: &Path,
+    file_by_name: &HashMap<String, RemoteFile>,
     force: bool,
 ) -> Result<(FileId, bool)> {
-    let file_name = file.x_file_name();
-    let mut file_id_by_name = get_files_hashmap(oac, asst_id).await?;
-
-    let file_id = file_id_by_name.remove(file_name);
+    let file_id = file_by_name.get(file.x_file_name()).map(|f| &f.id);
 
     // -- If not force and file already created, return early.
     if !force {
         if let Some(file_id) = file_id {
-            return Ok((file_id, false));
+            return Ok((file_id.clone(), false));
         }
     }
 
     // -- If we have old file_id, we delete the file.
     if let Some(file_id) = file_id {
-        // -- Delete the org file
-        let oa_files = oac.files();
-        if let Err(err) = oa_files.delete(&file_id.0).await {
-            println!(
-                "{} Can't delete file '{}'\n    cause: {:?}",
-                ico_err(),
-                file.to_string_lossy(),
-                err
-            );
-        }
-
-        // -- Delete the asst_file association
-        let oa_assts = oac.assistants();
-        let oa_assts_files = oa_assts.files(&asst_id.0);
-        if let Err(err) = oa_assts_files.delete(&file_id.0).await {
-            println!(
-                "{} Can't remove assistant file '{}'\n    cause: {:?}",
-                ico_err(),
-                file.x_file_name(),
-                err
-            );
-        }
+        delete_file(oac, asst_id, file_id, file.x_file_name()).await;
     }
 
-    // -- Upload and attach the file.
-    let term = Term::stdout();
-
-    // Print uploading.
-    term.write_line(&format!(
-        "{} Uploading file '{}'",
-        ico_uploading(),
-        file.x_file_name()
-    ))?;
-
-    // Upload file.
+    // -- Upload file.
     let oa_files = oac.files();
     let oa_file = oa_files
         .create(CreateFileRequest {
@@ -296,15 +673,7 @@ pub async fn upload_file_by_name(
         })
         .await?;
 
-    // Update print.
-    term.clear_last_lines(1)?;
-    term.write_line(&format!(
-        "{} Uploaded file '{}'",
-        ico_uploaded(),
-        file.x_file_name()
-    ))?;
-
-    // Attach file to assistant.
+    // -- Attach file to assistant.
     let oa_assts = oac.assistants();
     let oa_assts_files = oa_assts.files(&asst_id.0);
     let asst_file_obj = oa_assts_files
@@ -323,3 +692,25 @@ pub async fn upload_file_by_name(
 
     Ok((asst_file_obj.id.into(), true))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copied_msg_roundtrip() {
+        let answer = ThreadMsg {
+            role: MessageRole::Assistant,
+            created_at: 0,
+            run_id: None,
+            text: "It is 42.\n\nMore?".to_string(),
+            file_ids: Vec::new(),
+        };
+        let copied = copied_msg_text(answer.clone());
+        assert!(copied.starts_with(COPIED_ANSWER_PREFIX));
+        assert_eq!(uncopied_msg(MessageRole::User, copied), (MessageRole::Assistant, answer.text));
+
+        let question = "What is it?".to_string();
+        assert_eq!(uncopied_msg(MessageRole::User, question.clone()), (MessageRole::User, question));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::ais::assistant::RunRes;
+use crate::ais::OaClient;
+use crate::Result;
+use async_openai::types::{
+    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage,
+    ChatCompletionRequestUserMessageContent, CreateChatCompletionRequest, Role, RunCompletionUsage,
+};
+
+/// One-shot chat completion, outside of any thread (e.g., to summarize one).
+/// Returned as a `RunRes`, with the completion id as run id, for the usage log.
+pub async fn complete(open_ai_client: &OaClient, model: &str, system: &str, user: String) -> Result<RunRes> {
+    let request = CreateChatCompletionRequest {
+        model: model.to_string(),
+        messages: vec![
+            ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+                content: system.to_string(),
+                role: Role::System,
+                name: None,
+            }),
+            ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage {
+                content: ChatCompletionRequestUserMessageContent::Text(user),
+                role: Role::User,
+                name: None,
+            }),
+        ],
+        ..Default::default()
+    };
+    let res = open_ai_client.chat().create(request).await?;
+
+    let text = res
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .ok_or_else(|| "No content in the completion".to_string())?;
+
+    Ok(RunRes {
+        run_id: res.id,
+        text,
+        model: res.model,
+        usage: res.usage.map(|u| RunCompletionUsage {
+            completion_tokens: u.completion_tokens,
+            prompt_tokens: u.prompt_tokens,
+            total_tokens: u.total_tokens,
+        }),
+    })
+}
@@ -1,26 +1,25 @@
 pub mod assistant;
+pub mod chat;
 pub mod msg;
+pub mod steps;
 
 use async_openai::Client;
 use async_openai::config::OpenAIConfig;
 use crate::Result;
+use backoff::ExponentialBackoff;
 
-use dotenv::dotenv;
-use dotenv::var;
 use crate::utils::files::get_glob_set;
 
-const ENV_OPENAI_API_KEY: &str = "OPENAI_API_KEY";
-
 pub type OaClient = Client<OpenAIConfig>;
 
-pub fn new_op_client() -> Result<OaClient> {
-    dotenv().ok();
-    if var(ENV_OPENAI_API_KEY).is_ok() {
-        Ok(Client::new())
-    } else {
-        print!("No {} in env file", ENV_OPENAI_API_KEY);
-        Err("No OpenAI api key in env file".into())
+/// - `api_base` is `None` for the default OpenAI API base.
+/// - `backoff` is the retry policy of the rate limited calls.
+pub fn new_op_client(api_base: Option<&str>, api_key: &str, backoff: ExponentialBackoff) -> Result<OaClient> {
+    let mut config = OpenAIConfig::new().with_api_key(api_key);
+    if let Some(api_base) = api_base {
+        config = config.with_api_base(api_base);
     }
+    Ok(Client::with_config(config).with_backoff(backoff))
 }
 
 // DANGER ZONE - Make sure to triple check before calling. Not pub for now.
@@ -1,7 +1,6 @@
 use async_openai::types::{CreateMessageRequest, MessageContent, MessageObject};
 use crate::Result;
 
-#[ allow(unused)]
 pub fn user_msg(content: impl Into<String>) -> CreateMessageRequest {
     CreateMessageRequest {
         role: "user".to_string(),
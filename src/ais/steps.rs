@@ -0,0 +1,193 @@
+use crate::ais::assistant::ThreadId;
+use crate::ais::OaClient;
+use crate::utils::cli::{ico_check, ico_err, ico_info, txt_dim};
+use crate::Result;
+use async_openai::types::{
+    CodeInterpreterOutput, RunStatus, RunStepDetailsToolCalls, RunStepObject, StepDetails,
+};
+use chrono::{DateTime, Local};
+use std::collections::HashSet;
+
+/// Tool inputs/outputs longer than this are cut when printed.
+const MAX_DETAIL_LINES: usize = 20;
+
+/// A step of a run: the creation of the answer message, or tool calls.
+#[derive(Debug)]
+pub struct RunStep {
+    pub id: String,
+    pub status: RunStatus,
+    pub created_at: i64,
+    /// When it completed, failed, was cancelled or expired.
+    pub ended_at: Option<i64>,
+    pub tokens: Option<u32>,
+    pub calls: Vec<StepCall>,
+}
+
+#[derive(Debug)]
+pub enum StepCall {
+    Message { message_id: String },
+    /// The API does not tell what was retrieved.
+    Retrieval,
+    Code { input: String, outputs: Vec<String> },
+    Function { name: String, arguments: String, output: Option<String> },
+}
+
+impl RunStep {
+    /// E.g. `message`, or `retrieval, code_interpreter` for tool calls.
+    pub fn kind(&self) -> String {
+        let kinds: Vec<&str> = self
+            .calls
+            .iter()
+            .map(|call| match call {
+                StepCall::Message { .. } => "message",
+                StepCall::Retrieval => "retrieval",
+                StepCall::Code { .. } => "code_interpreter",
+                StepCall::Function { .. } => "function",
+            })
+            .collect();
+        kinds.join(", ")
+    }
+
+    fn is_done(&self) -> bool {
+        !matches!(self.status, RunStatus::Queued | RunStatus::InProgress)
+    }
+}
+
+impl From<RunStepObject> for RunStep {
+    fn from(step: RunStepObject) -> Self {
+        let calls = match step.step_details {
+            StepDetails::MessageCreation(details) => vec![StepCall::Message {
+                message_id: details.message_creation.message_id,
+            }],
+            StepDetails::ToolCalls(details) => details
+                .tool_calls
+                .into_iter()
+                .map(|call| match call {
+                    RunStepDetailsToolCalls::Code(code) => StepCall::Code {
+                        input: code.code_interpreter.input,
+                        outputs: code
+                            .code_interpreter
+                            .outputs
+                            .into_iter()
+                            .map(|output| match output {
+                                CodeInterpreterOutput::Log(log) => log.logs,
+                                CodeInterpreterOutput::Image(image) => format!("[image {}]", image.image.file_id),
+                            })
+                            .collect(),
+                    },
+                    RunStepDetailsToolCalls::Retrieval(_) => StepCall::Retrieval,
+                    RunStepDetailsToolCalls::Function(function) => StepCall::Function {
+                        name: function.function.name,
+                        arguments: function.function.arguments,
+                        output: function.function.output,
+                    },
+                })
+                .collect(),
+        };
+        let ended_at = step.completed_at.or(step.failed_at).or(step.cancelled_at).or(step.expired_at);
+
+        RunStep {
+            id: step.id,
+            status: step.status,
+            created_at: step.created_at as i64,
+            ended_at: ended_at.map(|at| at as i64),
+            tokens: step.usage.map(|usage| usage.total_tokens),
+            calls,
+        }
+    }
+}
+
+/// The steps of the run, oldest first.
+pub async fn list_run_steps(open_ai_client: &OaClient, thread_id: &ThreadId, run_id: &str) -> Result<Vec<RunStep>> {
+    let oa_threads = open_ai_client.threads();
+    let oa_runs = oa_threads.runs(thread_id.as_str());
+    let steps = oa_runs.steps(run_id).list(&[("order", "asc"), ("limit", "100")]).await?;
+
+    Ok(steps.data.into_iter().map(RunStep::from).collect())
+}
+
+/// Prints the steps of the run not printed yet (`printed`), as they end.
+/// The ones still in progress get a short line when first seen.
+pub(super) async fn print_new_steps(
+    open_ai_client: &OaClient,
+    thread_id: &ThreadId,
+    run_id: &str,
+    printed: &mut HashSet<String>,
+) -> Result<()> {
+    for step in list_run_steps(open_ai_client, thread_id, run_id).await? {
+        if step.is_done() {
+            if printed.insert(format!("{}:done", step.id)) {
+                print_step(&step);
+            }
+        } else if printed.insert(step.id.clone()) {
+            println!("  {}", txt_dim(format!("… {} in progress", step.kind())));
+        }
+    }
+    Ok(())
+}
+
+/// Prints the step header (kind, status, timing, tokens), then the tool inputs and outputs.
+pub fn print_step(step: &RunStep) {
+    let ico = match step.status {
+        RunStatus::Completed => ico_check(),
+        RunStatus::Queued | RunStatus::InProgress => ico_info(),
+        _ => ico_err(),
+    };
+    let at = DateTime::from_timestamp(step.created_at, 0)
+        .map(|at| at.with_timezone(&Local).format("%H:%M:%S").to_string())
+        .unwrap_or_default();
+    let mut header = format!("{} · {:?} · {at}", step.kind(), step.status);
+    if let Some(ended_at) = step.ended_at {
+        header.push_str(&format!(" ({}s)", ended_at - step.created_at));
+    }
+    if let Some(tokens) = step.tokens {
+        header.push_str(&format!(" · {tokens} tokens"));
+    }
+    println!("  {ico} {header}");
+
+    for call in step.calls.iter() {
+        match call {
+            StepCall::Message { message_id } => print_detail("message", message_id),
+            StepCall::Retrieval => print_detail("retrieval", "(the API does not report what was retrieved)"),
+            StepCall::Code { input, outputs } => {
+                print_detail("input", input);
+                for output in outputs {
+                    print_detail("output", output);
+                }
+            }
+            StepCall::Function { name, arguments, output } => {
+                print_detail("call", &format!("{name}({arguments})"));
+                print_detail("output", output.as_deref().unwrap_or("(not submitted)"));
+            }
+        }
+    }
+}
+
+fn print_detail(label: &str, text: &str) {
+    println!("      {}", txt_dim(format!("{label}:")));
+    for line in cut_lines(text, MAX_DETAIL_LINES) {
+        println!("        {}", txt_dim(line));
+    }
+}
+
+/// The first `max` lines of `text`, and a `…` line telling how many were cut.
+fn cut_lines(text: &str, max: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut res: Vec<String> = lines.iter().take(max).map(|l| l.to_string()).collect();
+    if lines.len() > max {
+        res.push(format!("… ({} more lines)", lines.len() - max));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_cut_lines() {
+        assert_eq!(cut_lines("a\nb", 2), vec!["a", "b"]);
+        assert_eq!(cut_lines("a\nb\nc\nd", 2), vec!["a", "b", "… (2 more lines)"]);
+        assert!(cut_lines("", 2).is_empty());
+    }
+}
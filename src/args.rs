@@ -0,0 +1,76 @@
+use clap::{Parser, Subcommand};
+
+/// Chat with an OpenAI assistant that knows your files.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Args {
+    /// Buddy directory (with the `buddy.toml`).
+    #[arg(short, long, default_value = "buddy")]
+    pub dir: String,
+
+    #[command(subcommand)]
+    pub cmd: Option<CliCmd>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCmd {
+    /// Chat with the buddy (default).
+    Chat,
+    /// Create a buddy for the project: `buddy.toml` with proposed file bundles,
+    /// a starter instructions file, and `.buddy/` in the `.gitignore`.
+    Init {
+        /// Buddy directory to create (default: the `--dir` one).
+        dir: Option<String>,
+        /// Buddy name (default: `<project>-buddy`).
+        #[arg(long)]
+        name: Option<String>,
+        /// Model (default: `gpt-4o`).
+        #[arg(long)]
+        model: Option<String>,
+        /// Do not prompt, accept all the proposed file bundles.
+        #[arg(short, long)]
+        yes: bool,
+        /// Overwrite an existing `buddy.toml`.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Upload the instructions and the new or changed file bundles.
+    Sync {
+        /// Show what would be created, updated or deleted, without changing anything.
+        #[arg(long)]
+        dry_run: bool,
+        /// Re-upload all the bundles, even unchanged ones.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the buddy config and report all its problems (exits with 1 on errors).
+    Validate {
+        /// Fail on warnings too.
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Export the current conversation of the buddy.
+    Export {
+        /// `md`, `json` or `html`.
+        #[arg(short, long, default_value = "md")]
+        format: String,
+        /// Output file.
+        #[arg(short, long)]
+        out: String,
+        /// Pull the full thread history from the API, instead of the local transcript.
+        #[arg(long)]
+        remote: bool,
+    },
+    /// Inspect the buddy config.
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCmd,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCmd {
+    /// Print the effective config (global config, `buddy.toml`, `BUDDY_*` env)
+    /// and where each value comes from.
+    Show,
+}
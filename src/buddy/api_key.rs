@@ -0,0 +1,88 @@
+use crate::Result;
+use derive_more::Display;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+pub(super) const ENV_OPENAI_API_KEY: &str = "OPENAI_API_KEY";
+
+/// Where the OpenAI API key of a buddy comes from (see `Config::api_key_source`).
+/// The key itself is never printed, nor part of the errors.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub(super) enum ApiKeySource {
+    #[display("env variable '{_0}'")]
+    Env(String),
+    #[display("file '{}'", _0.display())]
+    File(PathBuf),
+    #[display("command `{_0}`")]
+    Cmd(String),
+}
+
+impl ApiKeySource {
+    /// Reads the key (trimmed), running the command if needed.
+    pub fn resolve(&self) -> Result<String> {
+        let key = match self {
+            ApiKeySource::Env(name) => {
+                dotenv::dotenv().ok();
+                env::var(name).map_err(|_| format!("No OpenAI API key: env variable '{name}' is not set"))?
+            }
+            ApiKeySource::File(file) => fs::read_to_string(file)
+                .map_err(|err| format!("Cannot read the OpenAI API key file '{}': {err}", file.display()))?,
+            ApiKeySource::Cmd(cmd) => run_key_cmd(cmd)?,
+        };
+
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("Empty OpenAI API key from {self}").into());
+        }
+
+        Ok(key.to_string())
+    }
+}
+
+/// Runs `cmd` with the shell and returns its stdout. The stdin and stderr are
+/// the terminal ones, so the command can prompt (e.g., for a password).
+fn run_key_cmd(cmd: &str) -> Result<String> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    };
+
+    let output = command
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("Cannot run the API key command `{cmd}`: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("API key command `{cmd}` failed ({})", output.status).into());
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| format!("API key command `{cmd}` output is not UTF-8").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_file_and_cmd() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("key.txt");
+        fs::write(&file, "sk-from-file\n")?;
+
+        assert_eq!(ApiKeySource::File(file).resolve()?, "sk-from-file");
+        assert_eq!(ApiKeySource::Cmd("echo sk-from-cmd".to_string()).resolve()?, "sk-from-cmd");
+
+        let err = ApiKeySource::Cmd("exit 3".to_string()).resolve().unwrap_err().to_string();
+        assert!(err.starts_with("API key command `exit 3` failed"), "{err}");
+        let err = ApiKeySource::Env("BUDDY_TEST_NO_SUCH_KEY".to_string()).resolve().unwrap_err();
+        assert!(err.to_string().contains("'BUDDY_TEST_NO_SUCH_KEY' is not set"));
+        Ok(())
+    }
+}
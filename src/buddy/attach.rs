@@ -0,0 +1,126 @@
+use crate::utils::cli::ico_warn;
+use crate::utils::files::{expand_path_or_glob, is_glob, read_to_string, XFile};
+use crate::Result;
+use std::path::{Path, PathBuf};
+
+/// Above this size, a warning is shown when attaching a file.
+const LARGE_ATTACHMENT_BYTES: usize = 32 * 1024;
+
+/// Max characters of a message with its attachments inlined.
+/// Above it, the attachments are uploaded as message files instead.
+pub const MAX_INLINE_CHARS: usize = 30_000;
+
+/// Max files of an `@glob` mention. More have to be attached with `/attach`.
+const MAX_MENTION_FILES: usize = 20;
+
+/// A local file to send along with the next message.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+impl Attachment {
+    pub fn size(&self) -> usize {
+        self.content.len()
+    }
+}
+
+/// Loads the files matching `path_or_glob` (see `expand_path_or_glob`),
+/// warning about large ones.
+pub fn load_attachments(path_or_glob: &str) -> Result<Vec<Attachment>> {
+    let files = expand_path_or_glob(path_or_glob)?;
+    if files.is_empty() {
+        return Err(format!("No files matching '{path_or_glob}'").into());
+    }
+
+    read_attachments(files)
+}
+
+fn read_attachments(files: Vec<PathBuf>) -> Result<Vec<Attachment>> {
+    let mut attachments = Vec::new();
+    for path in files {
+        let content = read_to_string(&path)
+            .map_err(|e| format!("Cannot attach '{}': {e}", path.display()))?;
+        let attachment = Attachment { path, content };
+        warn_if_large(&attachment);
+        attachments.push(attachment);
+    }
+
+    Ok(attachments)
+}
+
+/// Loads the files mentioned as `@path` (or `@glob`) in `msg`.
+/// Mentions that are not a path nor match any file (e.g. `@someone`) are ignored,
+/// the ones that cannot be attached are reported.
+pub fn load_mentions(msg: &str) -> Vec<Attachment> {
+    let mentions = msg
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|m| m.trim_end_matches(&[',', ';', ':', '!', '?', ')', '\'', '"', '.'][..]))
+        .filter(|m| !m.is_empty());
+
+    let mut attachments = Vec::new();
+    for m in mentions {
+        match load_mention(m) {
+            Ok(atts) => attachments.extend(atts),
+            Err(err) => println!("{} @{m} not attached: {err}", ico_warn()),
+        }
+    }
+    attachments
+}
+
+/// The files of the mention `m`, none if nothing matches it.
+/// Globs from the project root (`**/…`) or matching more than `MAX_MENTION_FILES`
+/// are refused, as they are more likely a typo than a question about all those files.
+fn load_mention(m: &str) -> Result<Vec<Attachment>> {
+    if !is_glob(m) {
+        let path = Path::new(m);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        if path.is_dir() {
+            return Err(format!("'{m}' is a directory (use a glob like '{m}/*.rs')").into());
+        }
+        return read_attachments(expand_path_or_glob(m)?);
+    }
+
+    if m.starts_with("**") {
+        return Err("the glob covers the whole project (use /attach if that is intended)".into());
+    }
+    let files = expand_path_or_glob(m)?;
+    if files.len() > MAX_MENTION_FILES {
+        return Err(format!(
+            "{} files match, more than {MAX_MENTION_FILES} (use /attach if that is intended)",
+            files.len()
+        )
+        .into());
+    }
+    read_attachments(files)
+}
+
+/// Appends the attachments to `msg` as fenced code blocks.
+pub fn inline_attachments(msg: &str, attachments: &[Attachment]) -> String {
+    let mut content = msg.to_string();
+    for att in attachments {
+        content.push_str(&format!(
+            "\n\n--- attached file: {} ---\n```{}\n{}\n```",
+            att.path.display(),
+            att.path.x_extension(),
+            att.content.trim_end()
+        ));
+    }
+    content
+}
+
+fn warn_if_large(att: &Attachment) {
+    if att.size() > LARGE_ATTACHMENT_BYTES {
+        println!(
+            "{} '{}' is large ({} KB, ~{}k tokens)",
+            ico_warn(),
+            att.path.display(),
+            att.size() / 1024,
+            att.size() / 4 / 1000
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::ais::assistant::{self, RunRes, ThreadMsg};
+use crate::ais::chat;
+use crate::buddy::transcript::TranscriptEntry;
+use crate::buddy::{Buddy, Conv};
+use crate::Result;
+use async_openai::types::MessageRole;
+use chrono::Local;
+use std::fmt;
+
+const SUMMARY_SYSTEM: &str = "You summarize a conversation between a user and an assistant, for the \
+    assistant to continue it without the original messages. Keep the facts, the decisions, the names \
+    (files, functions, ...), the code that matters and the open questions. Be concise, use bullet points.";
+const SUMMARY_PREFIX: &str = "Summary of our earlier conversation:";
+/// Rough token estimate, as the API does not count the tokens of a thread.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Size of a thread, in messages and estimated tokens.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadSize {
+    pub msgs: usize,
+    pub tokens: usize,
+}
+
+impl ThreadSize {
+    fn of(msgs: &[ThreadMsg]) -> Self {
+        let chars: usize = msgs.iter().map(|m| m.text.chars().count()).sum();
+        Self {
+            msgs: msgs.len(),
+            tokens: chars.div_ceil(CHARS_PER_TOKEN),
+        }
+    }
+}
+
+impl fmt::Display for ThreadSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} messages, ~{} tokens", self.msgs, self.tokens)
+    }
+}
+
+/// Outcome of `compact_conv`.
+#[derive(Debug)]
+pub struct Compaction {
+    pub conv: Conv,
+    pub before: ThreadSize,
+    pub after: ThreadSize,
+    /// The summarization, for its usage.
+    pub summary_run: RunRes,
+}
+
+impl Buddy {
+    /// Prompt tokens of a run above which the conversation is compacted
+    /// (`compact.threshold_tokens`).
+    pub fn compact_threshold(&self) -> Option<u64> {
+        self.config.compact.threshold_tokens
+    }
+
+    /// Replaces `conv` with a new thread seeded with a summary of its messages,
+    /// but the last `compact.keep_turns` turns, which are kept as is.
+    /// The old thread is left as it is, but the files of the summarized messages
+    /// are deleted. The new conversation (with the same name) becomes the current one.
+    pub async fn compact_conv(&self, conv: &Conv) -> Result<Compaction> {
+        let msgs = assistant::list_thread_msgs(&self.open_ai_client, &conv.thread_id).await?;
+        let keep_turns = self.config.compact.keep_turns;
+        let cut = keep_cut(&msgs, keep_turns);
+        if cut == 0 {
+            return Err(format!("Nothing to compact, the conversation has {keep_turns} turn(s) or less").into());
+        }
+
+        // -- Summary of the older messages
+        let older: Vec<String> = msgs[..cut]
+            .iter()
+            .map(|m| {
+                let role = if m.role == MessageRole::User { "User" } else { "Assistant" };
+                format!("{role}:\n{}", m.text)
+            })
+            .collect();
+        let summary_run =
+            chat::complete(&self.open_ai_client, &self.config.model, SUMMARY_SYSTEM, older.join("\n\n")).await?;
+        let summary = format!("{SUMMARY_PREFIX}\n\n{}", summary_run.text.trim());
+
+        // -- New thread and transcript
+        let entries = self.entries_for(conv, &msgs)?;
+        let mut new_msgs = vec![ThreadMsg {
+            role: MessageRole::User,
+            created_at: Local::now().timestamp(),
+            run_id: None,
+            text: summary.clone(),
+            file_ids: Vec::new(),
+        }];
+        new_msgs.extend(msgs[cut..].iter().cloned());
+        let after = ThreadSize::of(&new_msgs);
+
+        let thread_id = assistant::copy_thread(&self.open_ai_client, new_msgs).await?;
+        let new_conv = Conv {
+            thread_id,
+            name: conv.name.clone(),
+        };
+        let transcript = self.transcript(&new_conv);
+        transcript.append(&TranscriptEntry::user(&summary, Vec::new()))?;
+        for entry in entries.iter().skip(cut) {
+            transcript.append(entry)?;
+        }
+        self.set_current_conv(&new_conv)?;
+        self.release_conv_files(conv).await;
+
+        Ok(Compaction {
+            conv: new_conv,
+            before: ThreadSize::of(&msgs),
+            after,
+            summary_run,
+        })
+    }
+}
+
+/// Index of the first message of the last `keep_turns` turns (a turn starts
+/// with a user message), 0 when there are not more turns than that.
+fn keep_cut(msgs: &[ThreadMsg], keep_turns: usize) -> usize {
+    if keep_turns == 0 {
+        return msgs.len();
+    }
+    msgs.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, m)| m.role == MessageRole::User)
+        .nth(keep_turns - 1)
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: &str) -> ThreadMsg {
+        ThreadMsg {
+            role: if role == "user" { MessageRole::User } else { MessageRole::Assistant },
+            created_at: 0,
+            run_id: None,
+            text: "12345678".to_string(),
+            file_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_keep_cut_and_size() {
+        // -- Turns: [u a] [u a a] [u a]
+        let msgs: Vec<ThreadMsg> =
+            ["user", "assistant", "user", "assistant", "assistant", "user", "assistant"].map(msg).into();
+
+        assert_eq!(keep_cut(&msgs, 0), 7);
+        assert_eq!(keep_cut(&msgs, 1), 5);
+        assert_eq!(keep_cut(&msgs, 2), 2);
+        assert_eq!(keep_cut(&msgs, 3), 0);
+        assert_eq!(keep_cut(&msgs, 5), 0);
+
+        assert_eq!(ThreadSize::of(&msgs).to_string(), "7 messages, ~14 tokens");
+    }
+}
@@ -1,22 +1,246 @@
-use serde::Deserialize;
-use crate::ais::assistant;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::ais::{assistant, new_op_client, OaClient};
+use crate::buddy::api_key::{ApiKeySource, ENV_OPENAI_API_KEY};
+use crate::utils::bundle::{BundleFormat, BundleLimits, CommentStyle};
+use crate::Result;
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
+use std::path::Path;
+use std::time::Duration;
 
+const DEFAULT_MAX_BUNDLE_KB: u64 = 2048;
+const DEFAULT_MAX_FILE_KB: u64 = 512;
+pub(super) const DEFAULT_MODEL: &str = "gpt-4o";
+pub(super) const DEFAULT_INSTRUCTIONS_FILE: &str = "instructions.md";
+
+/// The effective buddy config, merged from the global config, `buddy.toml`
+/// and the `BUDDY_*` env variables (see `LayeredConfig`).
 #[ allow(unused)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub(super) struct Config {
+    /// Defaults to the name of the buddy dir.
     pub name: String,
+    #[serde(default = "default_model")]
     pub model: String,
+    #[serde(default = "default_instructions_file")]
     pub instructions_file: String,
+    /// Base URL of the OpenAI API (default `https://api.openai.com/v1`).
+    pub api_base: Option<String>,
+    /// Name of the env variable with the API key (default `OPENAI_API_KEY`).
+    pub api_key_env: Option<String>,
+    /// File with the API key, relative to the buddy dir (`~/` for the home dir).
+    pub api_key_file: Option<String>,
+    /// Shell command printing the API key, e.g., a password manager CLI.
+    pub api_key_cmd: Option<String>,
+    /// Relative to the buddy dir. Bundle file paths are shown relative to it.
+    /// Defaults to the closest parent dir with a `.git`, or the buddy dir.
+    pub project_root: Option<String>,
+    #[serde(default)]
     pub file_bundles: Vec<FileBundle>,
+    /// Price per model name (or model name prefix), used for the cost reports.
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+    /// Monthly budget in USD, shown against the month total by `/usage`.
+    pub monthly_budget: Option<f64>,
+    /// Max number of bundle files uploaded at the same time (default 4).
+    pub upload_concurrency: Option<usize>,
+    /// Start the watch mode (see `/watch`) on launch.
+    #[serde(default)]
+    pub watch: bool,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub compact: CompactConfig,
+}
+
+/// `[output]`: how answers are printed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Answer wrap width, 0 to not wrap (default 80).
+    pub wrap_width: usize,
+    /// Colored output (default true).
+    pub color: bool,
+    /// Token and cost line after each answer (default true).
+    pub show_usage: bool,
+    /// Last turns (message and answer) summarized when a conversation is
+    /// loaded, 0 for none (default 2).
+    pub history_on_load: usize,
+    /// Print the steps of the runs (tool calls, ...) as they go, instead of
+    /// a spinner (default false).
+    pub verbose_steps: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            wrap_width: 80,
+            color: true,
+            show_usage: true,
+            history_on_load: 2,
+            verbose_steps: false,
+        }
+    }
+}
+
+/// `[retry]`: retries of the rate limited API calls, with exponential backoff.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub(super) struct RetryConfig {
+    /// Give up retrying after this time, 0 to not retry (default 60).
+    pub max_elapsed_secs: u64,
+    /// First retry delay, doubled at each retry (default 500).
+    pub initial_interval_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_elapsed_secs: 60,
+            initial_interval_ms: 500,
+        }
+    }
+}
+
+/// `[compact]`: compaction of long conversations (see `/compact`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub(super) struct CompactConfig {
+    /// Compact automatically after a run with more prompt tokens than this
+    /// (default none, only with `/compact`).
+    pub threshold_tokens: Option<u64>,
+    /// Last turns (message and answers) kept as is after the summary (default 2).
+    pub keep_turns: usize,
+}
+
+impl Default for CompactConfig {
+    fn default() -> Self {
+        Self {
+            threshold_tokens: None,
+            keep_turns: 2,
+        }
+    }
+}
+
+impl RetryConfig {
+    fn backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(Duration::from_millis(self.initial_interval_ms))
+            .with_max_elapsed_time(Some(Duration::from_secs(self.max_elapsed_secs)))
+            .build()
+    }
+}
+
+fn default_model() -> String {
+    DEFAULT_MODEL.to_string()
+}
+
+fn default_instructions_file() -> String {
+    DEFAULT_INSTRUCTIONS_FILE.to_string()
 }
 
 #[ allow(unused)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub(super) struct FileBundle {
     pub bundle_name: String,
     pub src_dir: String,
     pub dst_ext: String,
     pub src_globs: Vec<String>,
+    /// Globs relative to `src_dir` of files to leave out of the bundle
+    /// (on top of what `.gitignore`/`.ignore` already exclude).
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    /// Max size of one bundle file (KB). Bigger bundles are split into parts.
+    pub max_size_kb: Option<u64>,
+    /// Files bigger than this (KB) are skipped.
+    pub max_file_size_kb: Option<u64>,
+    #[serde(default)]
+    pub format: BundleFormatConfig,
+}
+
+/// `[file_bundles.format]`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(super) struct BundleFormatConfig {
+    /// Line comment prefix of the file headers. Defaults to the one of `dst_ext`.
+    pub comment: Option<String>,
+    /// Table of contents at the top of each bundle file (default false).
+    #[serde(default)]
+    pub toc: bool,
+    /// Line number prefixes (default false).
+    #[serde(default)]
+    pub line_numbers: bool,
+    /// Size and last modified time in the file headers (default false, as a
+    /// touch or checkout would change the bundle and re-upload it).
+    #[serde(default)]
+    pub file_meta: bool,
+}
+
+impl FileBundle {
+    pub fn limits(&self) -> BundleLimits {
+        BundleLimits {
+            max_bundle_size: self.max_size_kb.unwrap_or(DEFAULT_MAX_BUNDLE_KB) * 1024,
+            max_file_size: self.max_file_size_kb.unwrap_or(DEFAULT_MAX_FILE_KB) * 1024,
+        }
+    }
+
+    pub fn bundle_format(&self, project_root: &Path) -> BundleFormat {
+        let comment = match self.format.comment.as_ref() {
+            Some(prefix) => CommentStyle::Line(prefix.clone()),
+            None => CommentStyle::for_ext(&self.dst_ext),
+        };
+
+        BundleFormat {
+            comment,
+            base_dir: Some(project_root.to_path_buf()),
+            toc: self.format.toc,
+            line_numbers: self.format.line_numbers,
+            file_meta: self.format.file_meta,
+        }
+    }
+}
+
+/// USD per 1M tokens.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl Config {
+    /// Where the API key comes from. Only one of `api_key_env`, `api_key_file`
+    /// and `api_key_cmd` is set (see `validate`).
+    pub fn api_key_source(&self, dir: &Path) -> ApiKeySource {
+        if let Some(cmd) = self.api_key_cmd.as_ref() {
+            return ApiKeySource::Cmd(cmd.clone());
+        }
+        if let Some(file) = self.api_key_file.as_ref() {
+            let file = match (file.strip_prefix("~/"), dirs::home_dir()) {
+                (Some(rel), Some(home)) => home.join(rel),
+                _ => dir.join(file),
+            };
+            return ApiKeySource::File(file);
+        }
+        let name = self.api_key_env.as_deref().unwrap_or(ENV_OPENAI_API_KEY);
+        ApiKeySource::Env(name.to_string())
+    }
+
+    /// Client with the API key of `api_key_source` (of the buddy `dir`).
+    pub fn open_ai_client(&self, dir: &Path) -> Result<OaClient> {
+        let api_key = self.api_key_source(dir).resolve()?;
+        new_op_client(self.api_base.as_deref(), &api_key, self.retry.backoff())
+    }
+
+    /// Price for a run model, e.g. `gpt-4o-2024-05-13` matches a `gpt-4o` entry.
+    /// The longest matching entry wins.
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
 }
 
 impl From<&Config> for assistant::CreateConfig {
@@ -26,4 +250,4 @@ impl From<&Config> for assistant::CreateConfig {
             model: config.model.clone(),
         }
     }
-}
\ No newline at end of file
+}
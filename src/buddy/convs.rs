@@ -0,0 +1,167 @@
+use crate::ais::assistant::{self, FileId};
+use crate::buddy::transcript::TranscriptEntry;
+use crate::buddy::{Buddy, Conv, CONV_JSON};
+use crate::utils::cli::ico_warn;
+use crate::utils::files::{ensure_dir, load_from_json, save_to_json};
+use crate::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+const CONVS_DIR: &str = "convs";
+/// Name given to the current conversation when it is forked without a name.
+const DEFAULT_CONV_NAME: &str = "main";
+
+impl Buddy {
+    /// Creates a new conversation with the messages of `conv`, up to the
+    /// `upto`-th one (1 is the oldest, default all), named `name` (default
+    /// `fork-<n>`). `conv` is untouched, but gets named too (`main` by default)
+    /// to be switched back to with `switch_conv`.
+    /// Returns the fork, which becomes the current conversation.
+    pub async fn fork_conv(&self, conv: &mut Conv, name: Option<&str>, upto: Option<usize>) -> Result<Conv> {
+        let names = self.conv_names()?;
+        let name = match name {
+            Some(name) => {
+                check_conv_name(name)?;
+                if names.iter().any(|n| n == name) || conv.name() == Some(name) {
+                    return Err(format!("A conversation named '{name}' already exists").into());
+                }
+                name.to_string()
+            }
+            None => (1..)
+                .map(|n| format!("fork-{n}"))
+                .find(|n| !names.contains(n) && conv.name() != Some(n.as_str()))
+                .expect("a free fork name"),
+        };
+
+        // -- Messages to copy
+        let msgs = assistant::list_thread_msgs(&self.open_ai_client, &conv.thread_id).await?;
+        if msgs.is_empty() {
+            return Err("Nothing to fork, the conversation is empty".into());
+        }
+        let upto = upto.unwrap_or(msgs.len());
+        if upto == 0 || upto > msgs.len() {
+            return Err(format!("Expected a message index from 1 to {}, got {upto}", msgs.len()).into());
+        }
+
+        let entries: Vec<TranscriptEntry> = self.entries_for(conv, &msgs)?.into_iter().take(upto).collect();
+
+        let thread_id = assistant::copy_thread(&self.open_ai_client, msgs.into_iter().take(upto).collect()).await?;
+
+        // -- Register both
+        if conv.name.is_none() {
+            let default_name = std::iter::once(DEFAULT_CONV_NAME.to_string())
+                .chain((2..).map(|n| format!("{DEFAULT_CONV_NAME}-{n}")))
+                .find(|n| !names.contains(n) && *n != name)
+                .expect("a free conversation name");
+            conv.name = Some(default_name);
+            self.save_named_conv(conv)?;
+        }
+
+        let fork = Conv {
+            thread_id,
+            name: Some(name),
+        };
+        let transcript = self.transcript(&fork);
+        for entry in entries.iter() {
+            transcript.append(entry)?;
+        }
+        self.set_current_conv(&fork)?;
+
+        Ok(fork)
+    }
+
+    /// Names of the registered conversations, sorted.
+    pub fn conv_names(&self) -> Result<Vec<String>> {
+        let dir = self.convs_dir()?;
+        let mut names: Vec<String> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                file_name.strip_suffix(".json").map(String::from)
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Makes the registered conversation `name` the current one.
+    pub async fn switch_conv(&self, name: &str) -> Result<Conv> {
+        let file = self.convs_dir()?.join(format!("{name}.json"));
+        if !file.exists() {
+            return Err(format!("No conversation named '{name}'").into());
+        }
+        let conv: Conv = load_from_json(&file)?;
+        assistant::get_thread(&self.open_ai_client, &conv.thread_id)
+            .await
+            .map_err(|_| format!("Cannot find the thread of the conversation '{name}'"))?;
+
+        self.set_current_conv(&conv)?;
+        Ok(conv)
+    }
+
+    /// Saves `conv` as the current conversation (`conv.json`), and in the
+    /// registered ones when it is named.
+    pub(super) fn set_current_conv(&self, conv: &Conv) -> Result<()> {
+        save_to_json(self.data_sir()?.join(CONV_JSON), conv)?;
+        self.save_named_conv(conv)
+    }
+
+    /// Deletes the message files of `old`, once replaced as the current conversation,
+    /// that no conversation still has: the current one and the registered ones
+    /// (e.g. the forks of `old`, or `old` itself if it is named).
+    pub(super) async fn release_conv_files(&self, old: &Conv) {
+        match self.unused_conv_files(old) {
+            Ok(file_ids) => self.delete_msg_files(&file_ids).await,
+            Err(err) => println!("{} Can't find the attachments to delete\n    cause: {err}", ico_warn()),
+        }
+    }
+
+    fn unused_conv_files(&self, old: &Conv) -> Result<Vec<FileId>> {
+        let file_ids_of = |conv: &Conv| -> Result<Vec<String>> {
+            Ok(self.transcript(conv).entries()?.into_iter().flat_map(|e| e.file_ids).collect())
+        };
+        let old_ids = file_ids_of(old)?;
+        if old_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut convs: Vec<Conv> = load_from_json(self.data_sir()?.join(CONV_JSON)).into_iter().collect();
+        let convs_dir = self.convs_dir()?;
+        for name in self.conv_names()? {
+            convs.push(load_from_json(convs_dir.join(format!("{name}.json")))?);
+        }
+        let mut in_use = HashSet::new();
+        for conv in convs.iter() {
+            in_use.extend(file_ids_of(conv)?);
+        }
+
+        Ok(old_ids.into_iter().filter(|id| !in_use.contains(id)).map(FileId::from).collect())
+    }
+
+    fn save_named_conv(&self, conv: &Conv) -> Result<()> {
+        let Some(name) = conv.name.as_ref() else {
+            return Ok(());
+        };
+        save_to_json(self.convs_dir()?.join(format!("{name}.json")), conv)
+    }
+
+    fn convs_dir(&self) -> Result<PathBuf> {
+        let dir = self.data_sir()?.join(CONVS_DIR);
+        ensure_dir(&dir)?;
+        Ok(dir)
+    }
+}
+
+/// Names are file names: letters, digits, `-` and `_`.
+fn check_conv_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("Invalid conversation name '{name}' (letters, digits, '-' and '_' only)").into());
+    }
+    // `/fork 5` forks up to the 5th message.
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid conversation name '{name}' (not only digits)").into());
+    }
+    Ok(())
+}
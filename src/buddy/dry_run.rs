@@ -0,0 +1,180 @@
+use crate::ais::assistant::{self, AssistantId};
+use crate::buddy::sync::{BundleAction, LocalBundle, SyncPlan};
+use crate::buddy::{Buddy, LayeredConfig};
+use crate::utils::cli::{ico_info, txt_dim};
+use crate::utils::files::ensure_dir;
+use crate::Result;
+use derive_more::Display;
+use std::collections::HashMap;
+use std::path::Path;
+use std::{env, fs, process};
+
+/// Bundle file names need an assistant id, even before the assistant exists.
+const NEW_ASSISTANT_ID: &str = "new";
+
+/// What `sync` would do, computed without changing anything remotely.
+#[derive(Debug)]
+pub struct DryRun {
+    pub buddy_name: String,
+    pub assistant_exists: bool,
+    pub instructions: InstructionsDiff,
+    pub plan: SyncPlan,
+    /// Remote bundles no configured bundle produces anymore.
+    pub deleted: Vec<DeletedBundle>,
+}
+
+#[derive(Debug, Display, PartialEq, Eq)]
+pub enum InstructionsDiff {
+    #[display("unchanged")]
+    Unchanged,
+    #[display("differ from the assistant's, would be uploaded")]
+    Differ,
+    #[display("no instructions file")]
+    NoFile,
+}
+
+#[derive(Debug)]
+pub struct DeletedBundle {
+    pub bundle_name: String,
+    pub num_remote: usize,
+    pub remote_size: u64,
+}
+
+impl Buddy {
+    /// Plans the sync of the buddy in `dir` (see `upload_files`).
+    /// The bundles are built in a temp dir, and neither the assistant nor
+    /// `.buddy/` are created or modified.
+    pub async fn dry_run_from_dir(dir: impl AsRef<Path>, recreate: bool) -> Result<DryRun> {
+        let dir = dir.as_ref();
+        let config = LayeredConfig::load(dir)?.config;
+        let open_ai_client = config.open_ai_client(dir)?;
+        let assistant_obj = assistant::first_by_name(&open_ai_client, &config.name).await?;
+
+        let assistant_id: AssistantId = assistant_obj
+            .as_ref()
+            .map(|a| a.id.clone())
+            .unwrap_or_else(|| NEW_ASSISTANT_ID.to_string())
+            .into();
+        let buddy = Buddy {
+            dir: dir.to_path_buf(),
+            open_ai_client,
+            assistant_id,
+            config,
+        };
+
+        // -- Remote files
+        let file_by_name = match assistant_obj.as_ref() {
+            Some(_) => assistant::get_files_hashmap(&buddy.open_ai_client, &buddy.assistant_id).await?,
+            None => HashMap::new(),
+        };
+
+        // -- Local bundles (built in a temp dir)
+        let out_dir = env::temp_dir().join(format!("buddy-dry-run-{}", process::id()));
+        ensure_dir(&out_dir)?;
+        let local_bundles: Result<Vec<Option<LocalBundle>>> = buddy
+            .config
+            .file_bundles
+            .iter()
+            .map(|bundle| buddy.build_bundle(bundle, &out_dir))
+            .collect();
+        let plan = local_bundles.and_then(|local_bundles| {
+            let local_bundles: Vec<LocalBundle> = local_bundles.into_iter().flatten().collect();
+            buddy.plan_sync(&local_bundles, &file_by_name, recreate, true)
+        });
+        let _ = fs::remove_dir_all(&out_dir);
+        let plan = plan?;
+
+        // -- Deleted bundles
+        let mut deleted: Vec<DeletedBundle> = Vec::new();
+        for (name, remote) in plan.orphans.iter() {
+            let bundle_name = buddy.bundle_name_of(name).unwrap_or(name);
+            match deleted.iter_mut().find(|d| d.bundle_name == bundle_name) {
+                Some(d) => {
+                    d.num_remote += 1;
+                    d.remote_size += remote.bytes;
+                }
+                None => deleted.push(DeletedBundle {
+                    bundle_name: bundle_name.to_string(),
+                    num_remote: 1,
+                    remote_size: remote.bytes,
+                }),
+            }
+        }
+
+        // -- Instructions
+        let inst_file = buddy.instructions_file();
+        let instructions = if !inst_file.exists() {
+            InstructionsDiff::NoFile
+        } else {
+            let local = buddy.render_instructions()?;
+            let remote = assistant_obj.as_ref().and_then(|a| a.instructions.as_ref());
+            if remote == Some(&local) {
+                InstructionsDiff::Unchanged
+            } else {
+                InstructionsDiff::Differ
+            }
+        };
+
+        Ok(DryRun {
+            buddy_name: buddy.config.name,
+            assistant_exists: assistant_obj.is_some(),
+            instructions,
+            plan,
+            deleted,
+        })
+    }
+}
+
+impl DryRun {
+    pub fn print(&self) {
+        let assistant = if self.assistant_exists { "exists" } else { "would be created" };
+        println!("{} Dry run for '{}' (assistant {assistant})", ico_info(), self.buddy_name);
+        println!("  {:<12} {}", "instructions", self.instructions);
+
+        for b in self.plan.bundles.iter() {
+            let action = b.action();
+            let mut line = format!(
+                "  {:<12} {:<20} {:>4} files {:>6} KB",
+                action.to_string(),
+                b.bundle_name,
+                b.num_files,
+                b.size.div_ceil(1024)
+            );
+            if action != BundleAction::Create {
+                line.push_str(&format!(" ({})", size_delta(b.size, b.remote_size)));
+            }
+            if action == BundleAction::Update {
+                line.push_str(&format!(
+                    ", {} part(s) to upload, {} to remove",
+                    b.uploads.len(),
+                    b.stale.len()
+                ));
+            }
+            if !b.skipped.is_empty() {
+                line.push_str(&format!(", {} file(s) skipped", b.skipped.len()));
+            }
+            println!("{line}");
+        }
+
+        for d in self.deleted.iter() {
+            println!(
+                "  {:<12} {:<20} {:>4} remote file(s) ({})",
+                BundleAction::Delete.to_string(),
+                d.bundle_name,
+                d.num_remote,
+                size_delta(0, d.remote_size)
+            );
+        }
+
+        println!("{}", txt_dim("Nothing was changed (dry run).".to_string()));
+    }
+}
+
+/// `+4 KB`, `-12 KB` or `same size`.
+fn size_delta(local: u64, remote: u64) -> String {
+    match local.cmp(&remote) {
+        std::cmp::Ordering::Equal => "same size".to_string(),
+        std::cmp::Ordering::Greater => format!("+{} KB", (local - remote).div_ceil(1024)),
+        std::cmp::Ordering::Less => format!("-{} KB", (remote - local).div_ceil(1024)),
+    }
+}
@@ -0,0 +1,301 @@
+use crate::buddy::config::{DEFAULT_INSTRUCTIONS_FILE, DEFAULT_MODEL};
+use crate::buddy::BUDDY_TOML;
+use crate::utils::cli::{confirm, ico_check, ico_info, ico_warn, prompt_default, txt_dim};
+use crate::utils::files::{ensure_dir, read_to_string};
+use crate::Result;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const DATA_DIR_IGNORE: &str = ".buddy/";
+
+/// Options of `init`. Without `interactive`, the proposals are all accepted.
+#[derive(Debug, Default)]
+pub struct InitOptions {
+    pub name: Option<String>,
+    pub model: Option<String>,
+    /// Overwrite an existing `buddy.toml`.
+    pub force: bool,
+    pub interactive: bool,
+}
+
+/// A `[[file_bundles]]` entry proposed from what is found in the project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleProposal {
+    pub bundle_name: String,
+    /// Relative to the project root.
+    pub src_dir: PathBuf,
+    pub src_globs: Vec<String>,
+    pub exclude_globs: Vec<String>,
+    pub dst_ext: String,
+}
+
+/// Scaffolds a buddy in `dir`: a `buddy.toml` with the file bundles proposed
+/// for the project, a starter instructions file, and `.buddy/` in the `.gitignore`
+/// of the project.
+pub fn init_buddy(dir: &Path, options: InitOptions) -> Result<()> {
+    let toml_file = dir.join(BUDDY_TOML);
+    if toml_file.exists() && !options.force {
+        return Err(format!("'{}' already exists (use --force to overwrite)", toml_file.display()).into());
+    }
+
+    ensure_dir(dir)?;
+    let abs_dir = dir.canonicalize()?;
+    let project_root = find_project_root(&abs_dir);
+    println!(
+        "{} Initializing buddy in '{}' for project '{}'",
+        ico_info(),
+        dir.display(),
+        project_root.display()
+    );
+
+    // -- Name and model
+    let default_name = project_root
+        .file_name()
+        .map(|n| format!("{}-buddy", n.to_string_lossy()))
+        .unwrap_or_else(|| "buddy".to_string());
+    let name = match options.name {
+        Some(name) => name,
+        None if options.interactive => prompt_default("Buddy name", &default_name)?,
+        None => default_name,
+    };
+    let model = match options.model {
+        Some(model) => model,
+        None if options.interactive => prompt_default("Model", DEFAULT_MODEL)?,
+        None => DEFAULT_MODEL.to_string(),
+    };
+
+    // -- File bundles
+    let mut bundles = Vec::new();
+    for proposal in propose_bundles(&project_root) {
+        let accepted = !options.interactive
+            || confirm(
+                &format!(
+                    "Add bundle '{}' ({} {})?",
+                    proposal.bundle_name,
+                    proposal.src_dir.display(),
+                    proposal.src_globs.join(", ")
+                ),
+                true,
+            )?;
+        if accepted {
+            bundles.push(proposal);
+        }
+    }
+
+    // -- Write the files
+    fs::write(&toml_file, buddy_toml(&name, &model, &bundles, &abs_dir, &project_root))?;
+    println!("{} Created '{}'", ico_check(), toml_file.display());
+
+    let inst_file = dir.join(DEFAULT_INSTRUCTIONS_FILE);
+    if inst_file.exists() {
+        println!("{} Kept the existing '{}'", ico_warn(), inst_file.display());
+    } else {
+        fs::write(&inst_file, starter_instructions(&bundles))?;
+        println!("{} Created '{}'", ico_check(), inst_file.display());
+    }
+
+    let gitignore = project_root.join(".gitignore");
+    if add_to_gitignore(&gitignore, DATA_DIR_IGNORE)? {
+        println!("{} Added '{DATA_DIR_IGNORE}' to '{}'", ico_check(), gitignore.display());
+    }
+
+    println!(
+        "\n{}",
+        txt_dim("Next: set OPENAI_API_KEY, check the config with `validate`, then `sync` or `chat`.".to_string())
+    );
+
+    Ok(())
+}
+
+/// The bundles for what is found in `project_root`: Rust (`Cargo.toml`, with the
+/// manifests in their own bundle), JavaScript/TypeScript (`package.json`), and docs folders.
+pub fn propose_bundles(project_root: &Path) -> Vec<BundleProposal> {
+    let mut proposals = Vec::new();
+
+    if project_root.join("Cargo.toml").is_file() {
+        proposals.push(BundleProposal {
+            bundle_name: "rust-code".to_string(),
+            src_dir: PathBuf::from("."),
+            src_globs: vec!["**/*.rs".to_string()],
+            exclude_globs: vec!["target/**".to_string()],
+            dst_ext: "rs".to_string(),
+        });
+        proposals.push(BundleProposal {
+            bundle_name: "rust-manifests".to_string(),
+            src_dir: PathBuf::from("."),
+            src_globs: vec!["**/Cargo.toml".to_string()],
+            exclude_globs: vec!["target/**".to_string()],
+            dst_ext: "toml".to_string(),
+        });
+    }
+
+    if project_root.join("package.json").is_file() {
+        let ts = project_root.join("tsconfig.json").is_file();
+        proposals.push(BundleProposal {
+            bundle_name: if ts { "ts-code" } else { "js-code" }.to_string(),
+            src_dir: PathBuf::from("."),
+            src_globs: vec!["**/*.{js,jsx,ts,tsx,mjs,cjs}".to_string(), "package.json".to_string()],
+            exclude_globs: vec!["node_modules/**".to_string(), "dist/**".to_string(), "build/**".to_string()],
+            dst_ext: if ts { "ts" } else { "js" }.to_string(),
+        });
+    }
+
+    for docs_dir in ["docs", "doc", "documentation"] {
+        if project_root.join(docs_dir).is_dir() {
+            proposals.push(BundleProposal {
+                bundle_name: docs_dir.to_string(),
+                src_dir: PathBuf::from(docs_dir),
+                src_globs: vec!["**/*.md".to_string(), "**/*.txt".to_string()],
+                exclude_globs: Vec::new(),
+                dst_ext: "md".to_string(),
+            });
+        }
+    }
+
+    proposals
+}
+
+/// The closest ancestor of `dir` with a `.git`, or the parent of `dir`.
+fn find_project_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|d| d.join(".git").exists())
+        .or(dir.parent())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+fn buddy_toml(name: &str, model: &str, bundles: &[BundleProposal], dir: &Path, project_root: &Path) -> String {
+    let root_from_dir = relative_path(dir, project_root);
+    let mut out = format!(
+        "name = {}\nmodel = {}\ninstructions_file = {}\n# monthly_budget = 20.0\n",
+        toml_str(name),
+        toml_str(model),
+        toml_str(DEFAULT_INSTRUCTIONS_FILE)
+    );
+
+    for bundle in bundles {
+        let src_dir = normalize(&root_from_dir.join(&bundle.src_dir));
+        out.push_str("\n[[file_bundles]]\n");
+        out.push_str(&format!("bundle_name = {}\n", toml_str(&bundle.bundle_name)));
+        out.push_str(&format!("src_dir = {}\n", toml_str(&src_dir.to_string_lossy())));
+        out.push_str(&format!("src_globs = {}\n", toml_array(&bundle.src_globs)));
+        if !bundle.exclude_globs.is_empty() {
+            out.push_str(&format!("exclude_globs = {}\n", toml_array(&bundle.exclude_globs)));
+        }
+        out.push_str(&format!("dst_ext = {}\n", toml_str(&bundle.dst_ext)));
+    }
+
+    out
+}
+
+fn starter_instructions(bundles: &[BundleProposal]) -> String {
+    let mut out = String::from("You are {{buddy.name}}, a helpful assistant for this project.\n\n");
+    if !bundles.is_empty() {
+        out.push_str(
+            "Your knowledge files are bundles of the project files ({{buddy.bundles}}). \
+             Each file of a bundle starts with a comment giving its path.\n\
+             When answering, refer to the files by their path, and quote the relevant parts.\n",
+        );
+    }
+    out.push_str("If you do not know the answer, say so rather than guessing.\n");
+    out
+}
+
+/// Appends `line` to the `gitignore` file (created if needed), unless already there.
+/// Returns `true` if it was added.
+fn add_to_gitignore(gitignore: &Path, line: &str) -> Result<bool> {
+    let content = if gitignore.exists() { read_to_string(gitignore)? } else { String::new() };
+    let entry = line.trim_end_matches('/');
+    let exists = content.lines().map(str::trim).any(|l| {
+        let l = l.trim_start_matches("**/").trim_start_matches('/').trim_end_matches('/');
+        l == entry
+    });
+    if exists {
+        return Ok(false);
+    }
+
+    let sep = if content.is_empty() || content.ends_with('\n') { "" } else { "\n" };
+    fs::write(gitignore, format!("{content}{sep}{line}\n"))?;
+    Ok(true)
+}
+
+/// Path of `to` relative to `from` (both absolute), e.g., `..` or `../other`.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return to.iter().collect();
+    }
+
+    let mut path: PathBuf = std::iter::repeat_n("..", from.len() - common).collect();
+    path.extend(&to[common..]);
+    if path.as_os_str().is_empty() {
+        path.push(".");
+    }
+    path
+}
+
+/// Removes the `.` components, e.g., `../.` -> `..`.
+fn normalize(path: &Path) -> PathBuf {
+    let path: PathBuf = path.components().filter(|c| *c != Component::CurDir).collect();
+    if path.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        path
+    }
+}
+
+fn toml_str(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn toml_array(values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|v| toml_str(v)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buddy::layers::LayeredConfig;
+
+    #[test]
+    fn test_init_rust_project_with_docs() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        fs::create_dir(root.join(".git"))?;
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n")?;
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(root.join("src/main.rs"), "fn main() {}\n")?;
+        fs::create_dir(root.join("docs"))?;
+        fs::write(root.join("docs/intro.md"), "# Intro\n")?;
+        fs::write(root.join(".gitignore"), "target/")?;
+
+        let dir = root.join("buddy");
+        init_buddy(&dir, InitOptions { name: Some("demo-buddy".to_string()), ..Default::default() })?;
+
+        let (layered, problems) = LayeredConfig::check_from(None, &dir, std::iter::empty())?;
+        assert!(problems.is_empty(), "{problems:?}");
+        let config = layered.ok_or("no config")?.config;
+        assert_eq!(config.name, "demo-buddy");
+        let bundles: Vec<(&str, &str)> =
+            config.file_bundles.iter().map(|b| (b.bundle_name.as_str(), b.src_dir.as_str())).collect();
+        assert_eq!(bundles, [("rust-code", ".."), ("rust-manifests", ".."), ("docs", "../docs")]);
+
+        assert!(dir.join(DEFAULT_INSTRUCTIONS_FILE).is_file());
+        assert_eq!(fs::read_to_string(root.join(".gitignore"))?, "target/\n.buddy/\n");
+        assert!(!add_to_gitignore(&root.join(".gitignore"), DATA_DIR_IGNORE)?);
+
+        // -- Does not overwrite without `force`
+        assert!(init_buddy(&dir, InitOptions::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(relative_path(Path::new("/a/b/buddy"), Path::new("/a/b")), PathBuf::from(".."));
+        assert_eq!(relative_path(Path::new("/a/b"), Path::new("/a/b")), PathBuf::from("."));
+        assert_eq!(relative_path(Path::new("/a/b/c"), Path::new("/a/d")), PathBuf::from("../../d"));
+    }
+}
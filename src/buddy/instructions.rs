@@ -0,0 +1,187 @@
+use crate::buddy::Buddy;
+use crate::utils::files::{ensure_dir, list_files, load_from_json, read_to_string, save_to_json};
+use crate::utils::cli::ico_warn;
+use crate::utils::template::{render_file, Rendered};
+use crate::Result;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HISTORY_JSON: &str = "history.json";
+
+/// Versions of the uploaded instructions (`.buddy/instructions/`).
+/// Each version is kept as uploaded in `v<n>.md` and as written (before
+/// templating) in `v<n>.src.md`, next to the `history.json` index.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstructionsHistory {
+    #[serde(skip)]
+    dir: PathBuf,
+    versions: Vec<InstructionsVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstructionsVersion {
+    pub version: u32,
+    pub uploaded_at: String,
+    /// E.g., `rollback to v2`.
+    pub note: Option<String>,
+}
+
+impl InstructionsHistory {
+    /// Loads the history of `dir`, or starts an empty one.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let file = dir.join(HISTORY_JSON);
+        let mut history: Self = if file.exists() {
+            load_from_json(&file)?
+        } else {
+            Self::default()
+        };
+        history.dir = dir.to_path_buf();
+        Ok(history)
+    }
+
+    pub fn versions(&self) -> &[InstructionsVersion] {
+        &self.versions
+    }
+
+    pub fn latest(&self) -> Option<&InstructionsVersion> {
+        self.versions.last()
+    }
+
+    /// The instructions of `version`, as uploaded.
+    pub fn content(&self, version: u32) -> Result<String> {
+        if !self.versions.iter().any(|v| v.version == version) {
+            return Err(format!("No instructions version v{version}").into());
+        }
+        read_to_string(&self.version_file(version, ""))
+    }
+
+    /// The instructions file of `version`, before templating.
+    pub fn source(&self, version: u32) -> Result<String> {
+        let src_file = self.version_file(version, ".src");
+        if src_file.exists() {
+            read_to_string(&src_file)
+        } else {
+            self.content(version)
+        }
+    }
+
+    /// Adds `content` (from the `source` template) as a new version, unless it
+    /// is the latest one already. Returns the version of `content`.
+    pub fn record(&mut self, content: &str, source: &str, note: Option<String>) -> Result<u32> {
+        if let Some(latest) = self.latest() {
+            if self.content(latest.version)? == content {
+                return Ok(latest.version);
+            }
+        }
+
+        let version = self.latest().map(|v| v.version + 1).unwrap_or(1);
+        ensure_dir(&self.dir)?;
+        fs::write(self.version_file(version, ""), content)?;
+        fs::write(self.version_file(version, ".src"), source)?;
+        self.versions.push(InstructionsVersion {
+            version,
+            uploaded_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            note,
+        });
+        save_to_json(self.dir.join(HISTORY_JSON), self)?;
+
+        Ok(version)
+    }
+
+    fn version_file(&self, version: u32, suffix: &str) -> PathBuf {
+        self.dir.join(format!("v{version}{suffix}.md"))
+    }
+}
+
+impl Buddy {
+    /// Renders the instructions file (see `render_file`) with the variables:
+    /// - `buddy.name`, `buddy.model`, and `buddy.bundles` (the bundle names, comma separated).
+    /// - `files.<bundle_name>`: list of the files of the bundle, relative to the project root.
+    ///
+    /// Prints the template warnings (e.g. unknown variables, kept as is).
+    pub fn render_instructions(&self) -> Result<String> {
+        let rendered = self.render_instructions_with_includes()?;
+        print_render_warnings(&rendered);
+        Ok(rendered.text)
+    }
+
+    /// Same as `render_instructions`, with the files read (the instructions file,
+    /// then its includes) and the warnings, not printed.
+    pub fn render_instructions_with_includes(&self) -> Result<Rendered> {
+        render_file(&self.instructions_file(), &|name| self.instructions_var(name))
+    }
+
+    fn instructions_var(&self, name: &str) -> Result<Option<String>> {
+        let value = match name {
+            "buddy.name" => self.config.name.clone(),
+            "buddy.model" => self.config.model.clone(),
+            "buddy.bundles" => {
+                let names: Vec<&str> =
+                    self.config.file_bundles.iter().map(|b| b.bundle_name.as_str()).collect();
+                names.join(", ")
+            }
+            _ => {
+                let Some(bundle_name) = name.strip_prefix("files.") else {
+                    return Ok(None);
+                };
+                let Some(bundle) = self.config.file_bundles.iter().find(|b| b.bundle_name == bundle_name)
+                else {
+                    return Err(format!("no file bundle '{bundle_name}'").into());
+                };
+
+                let src_dir = self.dir.join(&bundle.src_dir);
+                if !src_dir.is_dir() {
+                    return Ok(Some(String::new()));
+                }
+                let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
+                let exclude_globs: Vec<&str> = bundle.exclude_globs.iter().map(AsRef::as_ref).collect();
+                let files = list_files(&src_dir, Some(&src_globs), Some(&exclude_globs))?;
+
+                let project_root = self.project_root().canonicalize()?;
+                let lines: Vec<String> = files
+                    .iter()
+                    .map(|f| {
+                        let f = f.canonicalize().unwrap_or_else(|_| f.clone());
+                        let path = f.strip_prefix(&project_root).unwrap_or(&f);
+                        format!("- {}", path.display())
+                    })
+                    .collect();
+                lines.join("\n")
+            }
+        };
+
+        Ok(Some(value))
+    }
+}
+
+pub(super) fn print_render_warnings(rendered: &Rendered) {
+    for warning in rendered.warnings.iter() {
+        println!("{} Instructions: {warning}", ico_warn());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_record_and_reload() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path().join("instructions");
+
+        let mut history = InstructionsHistory::load(&dir)?;
+        assert_eq!(history.record("first", "{{first}}", None)?, 1);
+        assert_eq!(history.record("first", "{{first}}", None)?, 1);
+        assert_eq!(history.record("second", "second", Some("rollback to v1".to_string()))?, 2);
+
+        let history = InstructionsHistory::load(&dir)?;
+        assert_eq!(history.versions().len(), 2);
+        assert_eq!(history.content(1)?, "first");
+        assert_eq!(history.source(1)?, "{{first}}");
+        assert_eq!(history.latest().and_then(|v| v.note.as_deref()), Some("rollback to v1"));
+        assert!(history.content(3).is_err());
+        Ok(())
+    }
+}
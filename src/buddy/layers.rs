@@ -0,0 +1,520 @@
+use crate::buddy::config::Config;
+use crate::buddy::BUDDY_TOML;
+use crate::utils::cli::{ico_check, ico_info, txt_dim};
+use crate::buddy::validate::{is_string_key, validate, ConfigProblem, Severity};
+use crate::utils::files::read_to_string;
+use crate::Result;
+use derive_more::Display;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use toml_edit::TableLike;
+
+/// In the user's config dir (e.g., `~/.config/` on Linux).
+const GLOBAL_CONFIG_FILE: &str = "ai-chatbox/config.toml";
+/// `BUDDY_MODEL` sets `model`, `BUDDY_OUTPUT__WRAP_WIDTH` sets `output.wrap_width`.
+const ENV_PREFIX: &str = "BUDDY_";
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum ConfigSource {
+    #[display("default")]
+    Default,
+    #[display("{}", _0.display())]
+    File(PathBuf),
+    #[display("env {_0}")]
+    Env(String),
+}
+
+/// The config layers, merged but not deserialized nor validated yet
+/// (lowest to highest priority):
+/// 1. the global config (`<config dir>/ai-chatbox/config.toml`), if any,
+/// 2. the `buddy.toml` of the buddy dir,
+/// 3. the `BUDDY_*` env variables (`__` separates nested keys).
+///
+/// Tables are merged key by key, other values (arrays included) are replaced.
+/// The `${VAR}` (or `${VAR:-default}`) in the strings of the files are replaced
+/// by the env variables, `$${` being a literal `${`.
+pub(super) struct ConfigLayers {
+    pub merged: Table,
+    /// Files found with their content, lowest priority first.
+    pub files: Vec<(PathBuf, String)>,
+    /// `${VAR}` without a value, with the path of the string referencing them.
+    pub unresolved: Vec<(Vec<String>, String)>,
+    /// Source of each value set by a layer, by key path.
+    sources: BTreeMap<Vec<String>, ConfigSource>,
+}
+
+impl ConfigLayers {
+    fn load_from(
+        global_file: Option<&Path>,
+        dir: &Path,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        let mut merged = Table::new();
+        let mut files = Vec::new();
+        let mut sources = BTreeMap::new();
+        let mut unresolved = Vec::new();
+        let vars: BTreeMap<String, String> = vars.collect();
+
+        // -- Files
+        let buddy_file = dir.join(BUDDY_TOML);
+        for (file, required) in [(global_file, false), (Some(buddy_file.as_path()), true)] {
+            let Some(file) = file.filter(|f| required || f.exists()) else {
+                continue;
+            };
+            let content = read_to_string(file)?;
+            let mut table: Table = content
+                .parse()
+                .map_err(|err| format!("{}: {err}", file.display()))?;
+            interpolate_table(&mut table, &mut Vec::new(), &vars, &mut unresolved);
+            let source = ConfigSource::File(file.to_path_buf());
+            merge(&mut merged, table, &[], &source, &mut sources);
+            files.push((file.to_path_buf(), content));
+        }
+
+        // -- Env
+        for (name, raw) in vars.into_iter().filter(|(name, _)| name.starts_with(ENV_PREFIX)) {
+            let path: Vec<String> =
+                name[ENV_PREFIX.len()..].to_lowercase().split("__").map(String::from).collect();
+            if path.iter().any(String::is_empty) {
+                continue;
+            }
+            set_env_value(&mut merged, &path, &raw);
+            sources.retain(|p, _| !p.starts_with(&path));
+            sources.insert(path, ConfigSource::Env(name));
+        }
+
+        // -- Defaults that depend on the dir
+        if !merged.contains_key("name") {
+            let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+            let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or("buddy");
+            merged.insert("name".to_string(), Value::String(name.to_string()));
+        }
+
+        Ok(Self {
+            merged,
+            files,
+            unresolved,
+            sources,
+        })
+    }
+
+    /// Source of the value at `path` (or of the closest parent set as a whole).
+    pub fn source_of(&self, path: &[String]) -> ConfigSource {
+        (1..=path.len())
+            .rev()
+            .find_map(|len| self.sources.get(&path[..len]))
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
+
+    /// Where the value at `path` is set: `file:line`, `env NAME`, or the
+    /// `buddy.toml` for the defaults.
+    pub fn location_of(&self, path: &[String]) -> String {
+        match self.source_of(path) {
+            ConfigSource::File(file) => {
+                let content = self.files.iter().find(|(f, _)| *f == file).map(|(_, c)| c.as_str());
+                match content.and_then(|content| key_line(content, path)) {
+                    Some(line) => format!("{}:{line}", file.display()),
+                    None => file.display().to_string(),
+                }
+            }
+            ConfigSource::Env(name) => format!("env {name}"),
+            ConfigSource::Default => self
+                .files
+                .last()
+                .map(|(f, _)| f.display().to_string())
+                .unwrap_or_else(|| BUDDY_TOML.to_string()),
+        }
+    }
+}
+
+/// The validated buddy config, from its `ConfigLayers`.
+pub struct LayeredConfig {
+    pub(super) config: Config,
+    layers: ConfigLayers,
+}
+
+impl LayeredConfig {
+    /// Loads and validates the config of `dir`. Warnings are printed, and
+    /// errors are all returned at once.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let (layered, problems) = Self::check(dir)?;
+        for problem in problems.iter().filter(|p| p.severity == Severity::Warning) {
+            println!("{problem}");
+        }
+
+        match layered {
+            Some(layered) if !problems.iter().any(|p| p.severity == Severity::Error) => Ok(layered),
+            _ => {
+                let errors: Vec<String> = problems
+                    .iter()
+                    .filter(|p| p.severity == Severity::Error)
+                    .map(|p| p.to_string())
+                    .collect();
+                Err(format!("Invalid config:\n{}", errors.join("\n")).into())
+            }
+        }
+    }
+
+    /// Loads the config of `dir` and returns all its problems (see `validate`).
+    /// The config is `None` when its structure is invalid.
+    pub fn check(dir: &Path) -> Result<(Option<Self>, Vec<ConfigProblem>)> {
+        dotenv::dotenv().ok();
+        let global_file = dirs::config_dir().map(|d| d.join(GLOBAL_CONFIG_FILE));
+        Self::check_from(global_file.as_deref(), dir, env::vars())
+    }
+
+    /// `validate`: prints all the problems of the config of `dir`, and fails
+    /// if any is an error (or a warning, when `strict`).
+    pub fn validate(dir: &Path, strict: bool) -> Result<()> {
+        let (_, problems) = Self::check(dir)?;
+        for problem in problems.iter() {
+            println!("{problem}");
+        }
+
+        let num_errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+        let num_warnings = problems.len() - num_errors;
+        if num_errors > 0 || (strict && num_warnings > 0) {
+            return Err(format!("{num_errors} error(s), {num_warnings} warning(s) in the config").into());
+        }
+        println!("{} Config is valid ({num_warnings} warning(s))", ico_check());
+
+        Ok(())
+    }
+
+    pub(super) fn check_from(
+        global_file: Option<&Path>,
+        dir: &Path,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<(Option<Self>, Vec<ConfigProblem>)> {
+        let layers = ConfigLayers::load_from(global_file, dir, vars)?;
+        let (config, problems) = validate(&layers, dir);
+        let layered = config.map(|config| Self { config, layers });
+        Ok((layered, problems))
+    }
+
+    /// The effective config, flattened to `(key, value, source)`.
+    pub fn entries(&self) -> Result<Vec<(String, String, ConfigSource)>> {
+        let value = Value::try_from(&self.config)?;
+        let mut leaves = Vec::new();
+        flatten(&value, &mut Vec::new(), &mut leaves);
+
+        let entries = leaves
+            .into_iter()
+            .map(|(path, value)| {
+                let source = self.layers.source_of(&path);
+                (display_key(&path), value, source)
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// `config show`
+    pub fn print(&self) -> Result<()> {
+        let files: Vec<String> = self.layers.files.iter().map(|(f, _)| f.display().to_string()).collect();
+        println!("{} Effective config of '{}' (from {})", ico_info(), self.config.name, files.join(", "));
+
+        let entries = self.entries()?;
+        let key_width = entries.iter().map(|(k, _, _)| k.len()).max().unwrap_or(0);
+        for (key, value, source) in entries {
+            println!("  {key:<key_width$} = {value}  {}", txt_dim(format!("# {source}")));
+        }
+
+        Ok(())
+    }
+}
+
+fn merge(
+    base: &mut Table,
+    over: Table,
+    prefix: &[String],
+    source: &ConfigSource,
+    sources: &mut BTreeMap<Vec<String>, ConfigSource>,
+) {
+    for (key, value) in over {
+        let mut path = prefix.to_vec();
+        path.push(key.clone());
+
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(over_table)) => {
+                merge(base_table, over_table, &path, source, sources);
+            }
+            (_, value) => {
+                base.insert(key, value);
+                sources.retain(|p, _| !p.starts_with(&path));
+                sources.insert(path, source.clone());
+            }
+        }
+    }
+}
+
+/// Sets `raw` at `path`, as a string if the key is a string in the config schema
+/// (or, for keys not in it, if the current value is a string), otherwise as a
+/// TOML value if it parses as one (e.g., `100`, `true`, `["a"]`).
+fn set_env_value(table: &mut Table, path: &[String], raw: &str) {
+    let (key, parents) = path.split_last().expect("path is not empty");
+    let mut table = table;
+    for parent in parents {
+        let entry = table
+            .entry(parent.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        table = entry.as_table_mut().expect("entry is a table");
+    }
+
+    let is_string = is_string_key(path).unwrap_or_else(|| matches!(table.get(key), Some(Value::String(_))));
+    let parsed = if is_string {
+        None
+    } else {
+        format!("v = {raw}").parse::<Table>().ok().and_then(|mut t| t.remove("v"))
+    };
+    let value = parsed.unwrap_or_else(|| Value::String(raw.to_string()));
+    table.insert(key.clone(), value);
+}
+
+fn interpolate_table(
+    table: &mut Table,
+    path: &mut Vec<String>,
+    vars: &BTreeMap<String, String>,
+    unresolved: &mut Vec<(Vec<String>, String)>,
+) {
+    for (key, value) in table.iter_mut() {
+        path.push(key.clone());
+        interpolate_value(value, path, vars, unresolved);
+        path.pop();
+    }
+}
+
+fn interpolate_value(
+    value: &mut Value,
+    path: &mut Vec<String>,
+    vars: &BTreeMap<String, String>,
+    unresolved: &mut Vec<(Vec<String>, String)>,
+) {
+    match value {
+        Value::String(s) => {
+            let (res, missing) = interpolate(s, vars);
+            unresolved.extend(missing.into_iter().map(|name| (path.clone(), name)));
+            *s = res;
+        }
+        Value::Array(items) => {
+            for (idx, item) in items.iter_mut().enumerate() {
+                path.push(idx.to_string());
+                interpolate_value(item, path, vars, unresolved);
+                path.pop();
+            }
+        }
+        Value::Table(table) => interpolate_table(table, path, vars, unresolved),
+        _ => (),
+    }
+}
+
+/// Replaces the `${VAR}` and `${VAR:-default}` of `s`. Returns the result and
+/// the variables without a value (replaced by an empty string).
+fn interpolate(s: &str, vars: &BTreeMap<String, String>) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(s.len());
+    let mut missing = Vec::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find("${") {
+        // -- Escaped `$${`
+        if rest[..start].ends_with('$') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            out.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let expr = &rest[start + 2..start + len];
+        rest = &rest[start + len + 1..];
+
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+        match (vars.get(name), default) {
+            (Some(value), _) => out.push_str(value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => missing.push(name.to_string()),
+        }
+    }
+    out.push_str(rest);
+
+    (out, missing)
+}
+
+fn flatten(value: &Value, path: &mut Vec<String>, leaves: &mut Vec<(Vec<String>, String)>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                path.push(key.clone());
+                flatten(value, path, leaves);
+                path.pop();
+            }
+        }
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_table) => {
+            for (idx, item) in items.iter().enumerate() {
+                path.push(idx.to_string());
+                flatten(item, path, leaves);
+                path.pop();
+            }
+        }
+        value => leaves.push((path.clone(), value.to_string())),
+    }
+}
+
+/// Line of the key at `path` in the TOML `content`, or of its closest parent
+/// when the key itself is not there (e.g., a missing field).
+fn key_line(content: &str, path: &[String]) -> Option<usize> {
+    let doc = toml_edit::ImDocument::parse(content).ok()?;
+    let mut table: &dyn TableLike = doc.as_table();
+    let mut span = None;
+    let mut segments = path.iter();
+    while let Some(segment) = segments.next() {
+        let (Some(key), Some(item)) = (table.key(segment), table.get(segment)) else {
+            break;
+        };
+        span = key.span().or(span);
+
+        if let Some(sub_table) = item.as_table_like() {
+            table = sub_table;
+        } else if let Some(tables) = item.as_array_of_tables() {
+            let idx = segments.next().and_then(|idx| idx.parse().ok());
+            let Some(sub_table) = idx.and_then(|idx| tables.get(idx)) else {
+                break;
+            };
+            span = sub_table.span().or(span);
+            table = sub_table;
+        } else {
+            break;
+        }
+    }
+
+    span.map(|span| content[..span.start].matches('\n').count() + 1)
+}
+
+/// `["prices", "gpt-4o", "prompt"]` -> `prices."gpt-4o".prompt`, array indices as `[n]`.
+pub(super) fn display_key(path: &[String]) -> String {
+    let mut key = String::new();
+    for segment in path {
+        if segment.parse::<usize>().is_ok() {
+            key.push_str(&format!("[{segment}]"));
+            continue;
+        }
+        if !key.is_empty() {
+            key.push('.');
+        }
+        let bare = segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if bare {
+            key.push_str(segment);
+        } else {
+            key.push_str(&format!("\"{segment}\""));
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn path(key: &str) -> Vec<String> {
+        key.split('.').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_layers_merge_and_sources() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let global_file = dir.path().join("global.toml");
+        fs::write(&global_file, "model = \"gpt-4o\"\n[output]\nwrap_width = 60\ncolor = false\n")?;
+        let buddy_dir = dir.path().join("my-buddy");
+        fs::create_dir(&buddy_dir)?;
+        fs::write(buddy_dir.join(BUDDY_TOML), "model = \"gpt-4o-mini\"\n[output]\ncolor = true\n")?;
+        let vars = [
+            ("BUDDY_OUTPUT__WRAP_WIDTH".to_string(), "100".to_string()),
+            ("BUDDY_MODEL".to_string(), "1.5".to_string()),
+            ("BUDDY_API_KEY_ENV".to_string(), "2024".to_string()),
+            ("BUDDY_MONTHLY_BUDGET".to_string(), "20".to_string()),
+            ("OTHER".to_string(), "x".to_string()),
+        ];
+
+        let (layered, problems) =
+            LayeredConfig::check_from(Some(&global_file), &buddy_dir, vars.into_iter())?;
+        let layered = layered.ok_or(format!("{problems:?}"))?;
+
+        let config = &layered.config;
+        assert_eq!(config.name, "my-buddy");
+        assert_eq!(config.api_key_env.as_deref(), Some("2024"));
+        assert_eq!(config.monthly_budget, Some(20.0));
+        assert_eq!(config.model, "1.5");
+        assert_eq!(config.output.wrap_width, 100);
+        assert!(config.output.color);
+        assert_eq!(config.instructions_file, "instructions.md");
+
+        assert_eq!(layered.layers.source_of(&path("name")), ConfigSource::Default);
+        assert_eq!(layered.layers.source_of(&path("model")), ConfigSource::Env("BUDDY_MODEL".to_string()));
+        assert_eq!(
+            layered.layers.source_of(&path("output.color")),
+            ConfigSource::File(buddy_dir.join(BUDDY_TOML))
+        );
+        assert_eq!(layered.layers.source_of(&path("retry.max_elapsed_secs")), ConfigSource::Default);
+        Ok(())
+    }
+
+    #[test]
+    fn test_layers_entries_keys() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(
+            dir.path().join(BUDDY_TOML),
+            "name = \"b\"\n[prices.\"gpt-3.5-turbo\"]\nprompt = 1.0\ncompletion = 2.0\n",
+        )?;
+
+        let (layered, problems) = LayeredConfig::check_from(None, dir.path(), std::iter::empty())?;
+        let layered = layered.ok_or(format!("{problems:?}"))?;
+        let entries = layered.entries()?;
+
+        let prompt = entries.iter().find(|(key, _, _)| key == "prices.\"gpt-3.5-turbo\".prompt");
+        assert!(matches!(prompt, Some((_, value, ConfigSource::File(_))) if value == "1.0"), "{entries:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_layers_interpolation() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(
+            dir.path().join(BUDDY_TOML),
+            "name = \"b-${TEAM}\"\nmodel = \"${MODEL:-gpt-4o}\"\napi_base = \"$${KEEP}\"\n\
+             api_key_env = \"${KEY_VAR}\"\n",
+        )?;
+        let vars = [("TEAM".to_string(), "acme".to_string())];
+
+        let (layered, problems) = LayeredConfig::check_from(None, dir.path(), vars.into_iter())?;
+
+        assert!(layered.is_none());
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert_eq!(problems[0].key, "api_key_env");
+        assert!(problems[0].location.ends_with("buddy.toml:4"), "{}", problems[0].location);
+        assert_eq!(problems[0].message, "env variable 'KEY_VAR' is not set");
+
+        let vars = [("TEAM".to_string(), "acme".to_string()), ("KEY_VAR".to_string(), "ACME_KEY".to_string())];
+        let (layered, problems) = LayeredConfig::check_from(None, dir.path(), vars.into_iter())?;
+        let config = layered.ok_or(format!("{problems:?}"))?.config;
+        assert_eq!(config.name, "b-acme");
+        assert_eq!(config.model, "gpt-4o");
+        assert_eq!(config.api_base.as_deref(), Some("${KEEP}"));
+        assert_eq!(config.api_key_env.as_deref(), Some("ACME_KEY"));
+        Ok(())
+    }
+}
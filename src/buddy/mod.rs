@@ -1,19 +1,54 @@
+mod api_key;
+mod attach;
+mod compact;
 mod config;
+mod convs;
+mod dry_run;
+mod init;
+mod instructions;
+mod layers;
+mod overrides;
+mod rewind;
+mod sync;
+mod transcript;
+mod usage;
+mod validate;
+mod watch;
+
+pub use self::attach::{load_attachments, load_mentions, Attachment};
+pub use self::config::OutputConfig;
+pub use self::init::{init_buddy, InitOptions};
+pub use self::instructions::InstructionsHistory;
+pub use self::layers::LayeredConfig;
+pub use self::overrides::{describe_overrides, parse_overrides};
+pub use self::transcript::{export_conv, export_transcript, ExportFormat, TranscriptEntry};
+pub use self::usage::{this_month, today, TokenUsage, UsageLog};
+pub use self::watch::BuddyWatcher;
 
 use std::fs;
 use std::path::{Path, PathBuf};
-use derive_more::{Deref, From};
+use derive_more::Deref;
 use serde::{Deserialize, Serialize};
-use crate::ais::assistant::{AssistantId, ThreadId};
-use crate::ais::{assistant, new_op_client, OaClient};
+use crate::ais::assistant::{ActiveRun, AssistantId, FileId, RunOverrides, RunRes, ThreadId};
+use crate::ais::msg::user_msg;
+use crate::ais::steps::{self, RunStep};
+use crate::ais::{assistant, OaClient};
 use crate::buddy::config::Config;
+use crate::buddy::sync::SYNC_LOCK;
 use crate::Result;
-use crate::utils::cli::ico_check;
-use crate::utils::files::{ensure_dir, load_from_json, load_from_toml, read_to_string, save_to_json};
+use crate::utils::cli::{ico_check, ico_uploaded, ico_warn};
+use crate::utils::files::{
+    ensure_dir, load_from_json, read_to_string, save_to_json,
+};
 
 const BUDDY_TOML: &str = "buddy.toml";
+/// Local data of the buddy, in the buddy dir.
+const DATA_DIR: &str = ".buddy";
+const CONV_JSON: &str = "conv.json";
+const USAGE_JSON: &str = "usage.json";
+const INSTRUCTIONS_DIR: &str = "instructions";
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Buddy {
     dir: PathBuf,
     open_ai_client: OaClient,
@@ -21,9 +56,26 @@ pub struct Buddy {
     config: Config,
 }
 
-#[derive(Debug, From, Deref, Deserialize, Serialize)]
+/// A conversation (thread) of the buddy. Forks and the conversations they
+/// were forked from are named, and registered in `.buddy/convs/`.
+#[derive(Debug, Deref, Deserialize, Serialize)]
 pub struct Conv {
+    #[deref]
     thread_id: ThreadId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+impl From<ThreadId> for Conv {
+    fn from(thread_id: ThreadId) -> Self {
+        Self { thread_id, name: None }
+    }
+}
+
+impl Conv {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 // Public Buddy functions
@@ -32,6 +84,10 @@ impl Buddy {
         &self.config.name
     }
 
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
     pub async fn init_from_dir(
         dir: impl AsRef<Path>,
         recreate_asst: bool,
@@ -39,10 +95,10 @@ impl Buddy {
         let dir = dir.as_ref();
 
         // -- Load from the directory
-        let config: Config = load_from_toml(dir.join(BUDDY_TOML))?;
+        let config = LayeredConfig::load(dir)?.config;
 
         // -- Get or Create the OpenAI Assistant
-        let open_ai_client = new_op_client()?;
+        let open_ai_client = config.open_ai_client(dir)?;
         let assistant_id =
             assistant::load_or_create_assistant(&open_ai_client, (&config).into(), recreate_asst).await?;
 
@@ -63,23 +119,58 @@ impl Buddy {
         Ok(buddy)
     }
 
+    /// Uploads the rendered instructions file (see `render_instructions`),
+    /// unless the assistant already runs with it.
+    /// Each upload is recorded in the instructions history (see `/instructions`).
+    /// Returns `true` if the instructions were uploaded.
     pub async fn upload_instructions(&self) -> Result<bool> {
-        let file = self.dir.join(&self.config.instructions_file);
-        if file.exists() {
-            let inst_content = read_to_string(&file)?;
-            assistant::upload_instructions(&self.open_ai_client, &self.assistant_id, inst_content)
-                .await?;
-            println!("{} Instructions uploaded", ico_check());
-            Ok(true)
-        } else {
-            Ok(false)
+        let _sync = SYNC_LOCK.lock().await;
+        let file = self.instructions_file();
+        if !file.exists() {
+            return Ok(false);
+        }
+
+        let inst_content = self.render_instructions()?;
+        let current = assistant::get_instructions(&self.open_ai_client, &self.assistant_id).await?;
+        if current.as_deref() == Some(inst_content.as_str()) {
+            println!("{} Instructions unchanged", ico_check());
+            return Ok(false);
         }
+
+        let source = read_to_string(&file)?;
+        let version = self.upload_instructions_content(inst_content, &source, None).await?;
+        println!("{} Instructions uploaded (v{version})", ico_check());
+
+        Ok(true)
+    }
+
+    pub fn instructions_file(&self) -> PathBuf {
+        self.dir.join(&self.config.instructions_file)
+    }
+
+    pub fn instructions_history(&self) -> Result<InstructionsHistory> {
+        InstructionsHistory::load(&self.data_sir()?.join(INSTRUCTIONS_DIR))
+    }
+
+    /// Restores the instructions file to `version` of the history, and uploads it.
+    /// Returns the (new) version recorded for the upload.
+    pub async fn rollback_instructions(&self, version: u32) -> Result<u32> {
+        let _sync = SYNC_LOCK.lock().await;
+        let history = self.instructions_history()?;
+        let inst_content = history.content(version)?;
+        let source = history.source(version)?;
+        fs::write(self.instructions_file(), &source)?;
+
+        let note = Some(format!("rollback to v{version}"));
+        self.upload_instructions_content(inst_content, &source, note).await
     }
 
     pub async fn load_or_create_conv(&self, recreate: bool) -> Result<Conv> {
-        let conv_file = self.data_sir()?.join("conv.json");
+        let conv_file = self.data_sir()?.join(CONV_JSON);
 
+        let mut old_conv = None;
         if recreate && conv_file.exists() { // Use exists() instead of exist()
+            old_conv = load_from_json::<Conv>(&conv_file).ok();
             fs::remove_file(&conv_file)?;
         }
 
@@ -87,7 +178,10 @@ impl Buddy {
             assistant::get_thread(&self.open_ai_client, &conv.thread_id)
                 .await
                 .map_err(|_| format!("Cannot find thread_id for {:?} ", conv))?;
-            print!("{} Conversation loaded", ico_check());
+            match conv.name() {
+                Some(name) => println!("{} Conversation '{name}' loaded", ico_check()),
+                None => println!("{} Conversation loaded", ico_check()),
+            }
             conv
         } else {
             let thread_id = assistant::create_thread(&self.open_ai_client).await?;
@@ -97,15 +191,139 @@ impl Buddy {
             conv
         };
 
+        if let Some(old_conv) = old_conv {
+            self.release_conv_files(&old_conv).await;
+        }
+
         Ok(conv)
     }
 
-    pub async fn chat(&self, conv: &Conv, msg: &str) -> Result<String> {
-        let res =
-            assistant::run_thread_msg(&self.open_ai_client, &self.assistant_id, &conv.thread_id, msg)
-                .await?;
+    /// `watch = true` in `buddy.toml`: start watching on launch.
+    pub fn watch_on_start(&self) -> bool {
+        self.config.watch
+    }
+
+    pub fn output(&self) -> &OutputConfig {
+        &self.config.output
+    }
 
-        Ok(res)
+    /// Model of the assistant (see `RunOverrides` to use another one for a run).
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    pub fn monthly_budget(&self) -> Option<f64> {
+        self.config.monthly_budget
+    }
+
+    /// Sends `msg` with its attachments, and runs the assistant on the conversation.
+    /// Attachments are inlined in the message when it stays under
+    /// `MAX_INLINE_CHARS`, otherwise they are uploaded as message files,
+    /// kept as long as the conversation (see `release_conv_files`).
+    /// The message is logged to the transcript once added to the thread.
+    pub async fn chat(
+        &self,
+        conv: &Conv,
+        msg: &str,
+        attachments: &[Attachment],
+        overrides: &RunOverrides,
+    ) -> Result<RunRes> {
+        let mut entry = TranscriptEntry::user(msg, attachments.iter().map(|a| a.path.display().to_string()).collect());
+        let inlined = attach::inline_attachments(msg, attachments);
+
+        let mut file_ids = Vec::new();
+        let msg = if inlined.chars().count() <= attach::MAX_INLINE_CHARS {
+            user_msg(inlined)
+        } else {
+            for att in attachments {
+                let file_id = assistant::upload_msg_file(&self.open_ai_client, &att.path).await;
+                let file_id = match file_id {
+                    Ok(file_id) => file_id,
+                    Err(err) => {
+                        self.delete_msg_files(&file_ids).await;
+                        return Err(err);
+                    }
+                };
+                println!("{} Uploaded attachment '{}'", ico_uploaded(), att.path.display());
+                file_ids.push(file_id);
+            }
+            entry.file_ids = file_ids.iter().map(|id| id.to_string()).collect();
+            let mut msg = user_msg(msg);
+            msg.file_ids = Some(entry.file_ids.clone());
+            msg
+        };
+
+        if let Err(err) = assistant::add_thread_msg(&self.open_ai_client, &conv.thread_id, msg).await {
+            // No message refers to them.
+            self.delete_msg_files(&file_ids).await;
+            return Err(err);
+        }
+        self.transcript(conv).append(&entry)?;
+
+        let verbose = self.config.output.verbose_steps;
+        assistant::run_thread(&self.open_ai_client, &self.assistant_id, &conv.thread_id, overrides, verbose).await
+    }
+
+    async fn delete_msg_files(&self, file_ids: &[FileId]) {
+        for file_id in file_ids {
+            if let Err(err) = assistant::delete_msg_file(&self.open_ai_client, file_id).await {
+                println!("{} Can't delete the uploaded attachment '{file_id}'\n    cause: {err}", ico_warn());
+            }
+        }
+    }
+
+    /// The run still going on `conv`, which blocks new messages until it ends.
+    pub async fn active_run(&self, conv: &Conv) -> Result<Option<ActiveRun>> {
+        assistant::active_run(&self.open_ai_client, &conv.thread_id).await
+    }
+
+    /// Waits for the answer of a run of `conv` (see `active_run`).
+    pub async fn await_run(&self, conv: &Conv, run_id: &str) -> Result<RunRes> {
+        assistant::await_run(&self.open_ai_client, &conv.thread_id, run_id, self.config.output.verbose_steps).await
+    }
+
+    /// Cancels a run of `conv`, and waits until it has ended.
+    pub async fn cancel_run(&self, conv: &Conv, run_id: &str) -> Result<()> {
+        assistant::cancel_run(&self.open_ai_client, &conv.thread_id, run_id).await?;
+        Ok(())
+    }
+
+    /// The id and the steps of the last run of `conv`, if any.
+    pub async fn last_run_steps(&self, conv: &Conv) -> Result<Option<(String, Vec<RunStep>)>> {
+        let Some(run_id) = assistant::last_run_id(&self.open_ai_client, &conv.thread_id).await? else {
+            return Ok(None);
+        };
+        let steps = steps::list_run_steps(&self.open_ai_client, &conv.thread_id, &run_id).await?;
+        Ok(Some((run_id, steps)))
+    }
+
+    /// Turns the live printing of the run steps on or off (`output.verbose_steps`).
+    pub fn set_verbose_steps(&mut self, on: bool) {
+        self.config.output.verbose_steps = on;
+    }
+
+    /// Prices the run and adds it to the usage log of this buddy.
+    /// Returns `None` when the API did not report usage for the run.
+    pub fn record_usage(&self, run_res: &RunRes) -> Result<Option<TokenUsage>> {
+        let Some(run_usage) = run_res.usage.as_ref() else {
+            return Ok(None);
+        };
+        let usage = TokenUsage::from_run(run_usage, self.config.price_for(&run_res.model));
+
+        let log_file = self.data_sir()?.join(USAGE_JSON);
+        let mut log = UsageLog::load(&log_file)?;
+        log.record(&run_res.model, usage);
+        log.save(&log_file)?;
+
+        Ok(Some(usage))
+    }
+
+    pub fn usage_log(&self) -> Result<UsageLog> {
+        UsageLog::load(&self.data_sir()?.join(USAGE_JSON))
+    }
+
+    pub fn has_price_for(&self, model: &str) -> bool {
+        self.config.price_for(model).is_some()
     }
 }
 
@@ -113,14 +331,43 @@ impl Buddy {
 #[ allow(unused)]
 impl Buddy {
     fn data_sir(&self) -> Result<PathBuf> {
-        let data_dir = self.dir.join(".buddy");
+        let data_dir = self.dir.join(DATA_DIR);
         ensure_dir(&data_dir)?;
         Ok(data_dir)
     }
 
+    /// See `Config::project_root`.
+    fn project_root(&self) -> PathBuf {
+        if let Some(project_root) = self.config.project_root.as_ref() {
+            return self.dir.join(project_root);
+        }
+
+        let dir = self.dir.canonicalize().unwrap_or_else(|_| self.dir.clone());
+        dir.ancestors()
+            .find(|d| d.join(".git").exists())
+            .map(Path::to_path_buf)
+            .unwrap_or(dir)
+    }
+
     fn data_files_sir(&self) -> Result<PathBuf> {
-        let dir = self.data_sir()?.join("files");
+        let dir = self.data_files_path();
         ensure_dir(&dir)?;
         Ok(dir)
     }
+
+    async fn upload_instructions_content(
+        &self,
+        inst_content: String,
+        source: &str,
+        note: Option<String>,
+    ) -> Result<u32> {
+        assistant::upload_instructions(&self.open_ai_client, &self.assistant_id, inst_content.clone())
+            .await?;
+        self.instructions_history()?.record(&inst_content, source, note)
+    }
+
+    /// `.buddy/files`, without creating it.
+    fn data_files_path(&self) -> PathBuf {
+        self.dir.join(DATA_DIR).join("files")
+    }
 }
\ No newline at end of file
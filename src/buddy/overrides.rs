@@ -0,0 +1,130 @@
+use crate::ais::assistant::{RunOverrides, RunTool};
+use crate::Result;
+
+/// Parses the `key=value` overrides of `/with`, e.g.
+/// `model=gpt-4o-mini add="Answer in one sentence" tools=none`.
+/// Values with spaces are quoted (`"` or `'`).
+pub fn parse_overrides(text: &str) -> Result<RunOverrides> {
+    let mut overrides = RunOverrides::default();
+    for token in split_quoted(text)? {
+        let Some((key, value)) = token.split_once('=') else {
+            return Err(format!("Expected key=value, got '{token}'").into());
+        };
+        if value.is_empty() {
+            return Err(format!("Missing value for '{key}'").into());
+        }
+        match key {
+            "model" => overrides.model = Some(value.to_string()),
+            "instructions" | "inst" => overrides.instructions = Some(value.to_string()),
+            "additional_instructions" | "add" => overrides.additional_instructions = Some(value.to_string()),
+            "tools" => overrides.tools = Some(parse_tools(value)?),
+            "temp" | "temperature" | "top_p" => {
+                return Err(format!(
+                    "'{key}' cannot be overridden, the Assistants API (v1) runs have no sampling settings"
+                )
+                .into())
+            }
+            _ => return Err(format!("Unknown override '{key}' (model, instructions, add, tools)").into()),
+        }
+    }
+    Ok(overrides)
+}
+
+/// One line description of `overrides`, e.g. `model=gpt-4o-mini tools=none`.
+pub fn describe_overrides(overrides: &RunOverrides) -> String {
+    let mut parts = Vec::new();
+    if let Some(model) = overrides.model.as_ref() {
+        parts.push(format!("model={model}"));
+    }
+    if let Some(instructions) = overrides.instructions.as_ref() {
+        parts.push(format!("instructions={instructions:?}"));
+    }
+    if let Some(add) = overrides.additional_instructions.as_ref() {
+        parts.push(format!("add={add:?}"));
+    }
+    if let Some(tools) = overrides.tools.as_ref() {
+        let tools = if tools.is_empty() {
+            "none".to_string()
+        } else {
+            tools.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(",")
+        };
+        parts.push(format!("tools={tools}"));
+    }
+    parts.join(" ")
+}
+
+/// `none`, or a comma separated list of `retrieval` and `code_interpreter`.
+fn parse_tools(value: &str) -> Result<Vec<RunTool>> {
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+    value
+        .split(',')
+        .map(|name| match name.trim() {
+            "retrieval" => Ok(RunTool::Retrieval),
+            "code_interpreter" | "code" => Ok(RunTool::CodeInterpreter),
+            other => Err(format!("Unknown tool '{other}' (retrieval, code_interpreter or none)").into()),
+        })
+        .collect()
+}
+
+/// Splits on whitespace, but within quotes (which are removed).
+fn split_quoted(text: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_token = false;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => token.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                token.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("Unclosed quote".into());
+    }
+    if in_token {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overrides() -> Result<()> {
+        let overrides = parse_overrides(r#"model=gpt-4o-mini add="Be brief, really" tools=none"#)?;
+        assert_eq!(overrides.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(overrides.additional_instructions.as_deref(), Some("Be brief, really"));
+        assert_eq!(overrides.tools, Some(Vec::new()));
+        assert_eq!(
+            describe_overrides(&overrides),
+            r#"model=gpt-4o-mini add="Be brief, really" tools=none"#
+        );
+
+        let overrides = parse_overrides("tools=retrieval,code")?;
+        assert_eq!(overrides.tools, Some(vec![RunTool::Retrieval, RunTool::CodeInterpreter]));
+
+        assert!(parse_overrides("temp=0.2").is_err());
+        assert!(parse_overrides("model").is_err());
+        assert!(parse_overrides("add='unclosed").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use crate::ais::assistant::{self, RunOverrides, RunRes, ThreadMsg};
+use crate::buddy::transcript::{Role, TranscriptEntry};
+use crate::buddy::{Buddy, Conv};
+use crate::Result;
+use async_openai::types::MessageRole;
+
+/// Added to the run of `retry`, as the rejected answer stays in the thread.
+const RETRY_INSTRUCTIONS: &str =
+    "The user was not satisfied with your last answer. Answer their last message again, differently.";
+
+/// The last user message removed by `undo`, to be edited and sent again.
+#[derive(Debug)]
+pub struct UndoneMsg {
+    pub text: String,
+    /// Paths of the files that were attached to it.
+    pub attachments: Vec<String>,
+}
+
+impl Buddy {
+    /// Runs the assistant again on `conv`, without a new user message.
+    pub async fn retry(&self, conv: &Conv, overrides: &RunOverrides) -> Result<RunRes> {
+        let (msgs, _) = assistant::list_thread_msgs_page(&self.open_ai_client, &conv.thread_id, 1, None).await?;
+        if msgs.is_empty() {
+            return Err("Nothing to retry, the conversation is empty".into());
+        }
+
+        let additional_instructions = match overrides.additional_instructions.as_ref() {
+            Some(add) => format!("{add}\n\n{RETRY_INSTRUCTIONS}"),
+            None => RETRY_INSTRUCTIONS.to_string(),
+        };
+        let overrides = RunOverrides {
+            additional_instructions: Some(additional_instructions),
+            ..overrides.clone()
+        };
+        let verbose = self.config.output.verbose_steps;
+        assistant::run_thread(&self.open_ai_client, &self.assistant_id, &conv.thread_id, &overrides, verbose).await
+    }
+
+    /// The last user message of `conv`, as typed (the thread has the attachments inlined).
+    pub async fn last_user_msg(&self, conv: &Conv) -> Result<UndoneMsg> {
+        Ok(self.rewind_point(conv).await?.msg)
+    }
+
+    /// Removes the last user message of `conv` and the answers after it.
+    /// As messages cannot be deleted, the thread is copied without them
+    /// (see `assistant::copy_thread`): returns the new conversation, which
+    /// becomes the current one once built, with the removed message.
+    /// The old thread is left as it is, but the files only it has are deleted.
+    pub async fn undo(&self, conv: &Conv) -> Result<(Conv, UndoneMsg)> {
+        let RewindPoint { msgs, idx, entries, entry_idx, msg } = self.rewind_point(conv).await?;
+
+        let keep = msgs.into_iter().take(idx).collect();
+        let thread_id = assistant::copy_thread(&self.open_ai_client, keep).await?;
+        let new_conv = Conv {
+            thread_id,
+            name: conv.name.clone(),
+        };
+        let transcript = self.transcript(&new_conv);
+        for entry in entries.iter().take(entry_idx.unwrap_or(0)) {
+            transcript.append(entry)?;
+        }
+        self.set_current_conv(&new_conv)?;
+        self.release_conv_files(conv).await;
+
+        Ok((new_conv, msg))
+    }
+
+    async fn rewind_point(&self, conv: &Conv) -> Result<RewindPoint> {
+        let msgs = assistant::list_thread_msgs(&self.open_ai_client, &conv.thread_id).await?;
+        let Some(idx) = msgs.iter().rposition(|m| m.role == MessageRole::User) else {
+            return Err("No message in the conversation".into());
+        };
+
+        let entries = self.transcript(conv).entries()?;
+        let entry_idx = entries.iter().rposition(|e| e.role == Role::User);
+        let msg = match entry_idx.map(|i| &entries[i]) {
+            Some(entry) => UndoneMsg {
+                text: entry.text.clone(),
+                attachments: entry.attachments.clone(),
+            },
+            None => UndoneMsg {
+                text: msgs[idx].text.clone(),
+                attachments: Vec::new(),
+            },
+        };
+
+        Ok(RewindPoint { msgs, idx, entries, entry_idx, msg })
+    }
+}
+
+/// Where `undo` cuts the thread and the transcript: at their last user message.
+struct RewindPoint {
+    msgs: Vec<ThreadMsg>,
+    idx: usize,
+    entries: Vec<TranscriptEntry>,
+    entry_idx: Option<usize>,
+    msg: UndoneMsg,
+}
@@ -0,0 +1,525 @@
+use crate::ais::assistant::{self, RemoteFile};
+use crate::buddy::config::FileBundle;
+use crate::buddy::{Buddy, DATA_DIR};
+use crate::utils::bundle::{bundle_to_files, BundleReport, SkippedFile};
+use crate::utils::cli::{ico_check, ico_deleted_ok, ico_err, ico_warn, new_progress_bar};
+use crate::utils::files::{list_files, load_from_json, save_to_json, XFile};
+use crate::Result;
+use derive_more::Display;
+use futures::stream::{self, StreamExt};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+const DEFAULT_UPLOAD_CONCURRENCY: usize = 4;
+/// What was uploaded, compared against by the next syncs (in the buddy data dir).
+const UPLOADED_JSON: &str = "uploaded.json";
+
+/// Held while syncing the bundles or the instructions, as the syncs of the
+/// commands and the ones of the watch thread write the same `.buddy/` files.
+/// The watch skips its sync while a command holds it (see `Buddy::watch`).
+pub(super) static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// What a sync did, per bundle.
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub bundles: Vec<BundleSync>,
+}
+
+#[derive(Debug)]
+pub struct BundleSync {
+    pub bundle_name: String,
+    pub num_files: usize,
+    pub num_parts: usize,
+    pub size: u64,
+    pub num_uploaded: u32,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl SyncSummary {
+    pub fn print(&self) {
+        for b in self.bundles.iter() {
+            let parts = if b.num_parts > 1 { format!(", {} parts", b.num_parts) } else { String::new() };
+            println!(
+                "{} Bundle '{}': {} files ({} KB{}), {} uploaded",
+                ico_check(),
+                b.bundle_name,
+                b.num_files,
+                b.size.div_ceil(1024),
+                parts,
+                b.num_uploaded
+            );
+            for skipped in b.skipped.iter() {
+                println!(
+                    "    {} skipped '{}' ({})",
+                    ico_warn(),
+                    skipped.path.display(),
+                    skipped.reason
+                );
+            }
+        }
+    }
+}
+
+/// What a sync does to the remote files, computed before touching them.
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    pub bundles: Vec<BundlePlan>,
+    /// Remote bundle files of this assistant that no configured bundle
+    /// produces anymore (e.g., bundle removed from `buddy.toml`).
+    pub orphans: Vec<(String, RemoteFile)>,
+}
+
+#[derive(Debug)]
+pub struct BundlePlan {
+    pub bundle_name: String,
+    pub num_files: usize,
+    pub num_parts: usize,
+    pub size: u64,
+    /// Number and total size of the remote files of this bundle.
+    pub num_remote: usize,
+    pub remote_size: u64,
+    /// Parts to upload, new or replacing a remote file.
+    pub uploads: Vec<PathBuf>,
+    /// Remote parts (or unsplit bundle) not produced by this build.
+    pub stale: Vec<(String, RemoteFile)>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum BundleAction {
+    #[display("create")]
+    Create,
+    #[display("update")]
+    Update,
+    #[display("delete")]
+    Delete,
+    #[display("unchanged")]
+    Unchanged,
+}
+
+impl BundlePlan {
+    pub fn action(&self) -> BundleAction {
+        if self.num_remote == 0 {
+            BundleAction::Create
+        } else if self.uploads.is_empty() && self.stale.is_empty() {
+            BundleAction::Unchanged
+        } else {
+            BundleAction::Update
+        }
+    }
+}
+
+/// A bundle file (or part) as uploaded: the remote file, and the hash of its content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct UploadedPart {
+    pub file_id: String,
+    pub hash: u64,
+}
+
+/// A bundle built locally, not uploaded yet.
+pub(super) struct LocalBundle {
+    pub bundle_name: String,
+    /// Unsplit bundle file (parts are `<stem>-partN.<ext>`).
+    pub bundle_file: PathBuf,
+    /// The parts of this bundle uploaded by the previous syncs, by file name.
+    pub uploaded: HashMap<String, UploadedPart>,
+    pub report: BundleReport,
+}
+
+impl LocalBundle {
+    /// True if `name` is a remote file of this bundle (whole bundle or part).
+    pub fn owns_remote(&self, name: &str) -> bool {
+        let stem = self.bundle_file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        name == self.bundle_file.x_file_name() || name.starts_with(&format!("{stem}-part"))
+    }
+
+    /// Compares this build with the remote files of the bundle.
+    /// A part is uploaded when it is not remote yet, when `recreate` is `true`,
+    /// or when its content differs from the one uploaded (or it is not known,
+    /// e.g. the remote file was uploaded by another buddy dir).
+    pub fn plan(&self, file_by_name: &HashMap<String, RemoteFile>, recreate: bool) -> Result<BundlePlan> {
+        let mut uploads = Vec::new();
+        for part in self.report.parts.iter() {
+            let name = part.x_file_name();
+            let upload = match file_by_name.get(name) {
+                None => true,
+                Some(_) if recreate => true,
+                Some(remote) => match self.uploaded.get(name) {
+                    Some(uploaded) if uploaded.file_id == remote.id.as_str() => content_hash(part)? != uploaded.hash,
+                    _ => true,
+                },
+            };
+            if upload {
+                uploads.push(part.clone());
+            }
+        }
+
+        let part_names: Vec<&str> = self.report.parts.iter().map(|p| p.x_file_name()).collect();
+        let remotes: Vec<(&String, &RemoteFile)> =
+            file_by_name.iter().filter(|(name, _)| self.owns_remote(name)).collect();
+        let stale = remotes
+            .iter()
+            .filter(|(name, _)| !part_names.contains(&name.as_str()))
+            .map(|(name, remote)| ((*name).clone(), (*remote).clone()))
+            .collect();
+
+        Ok(BundlePlan {
+            bundle_name: self.bundle_name.clone(),
+            num_files: self.report.num_files,
+            num_parts: self.report.parts.len(),
+            size: self.report.size,
+            num_remote: remotes.len(),
+            remote_size: remotes.iter().map(|(_, r)| r.bytes).sum(),
+            uploads,
+            stale,
+            skipped: self.report.skipped.clone(),
+        })
+    }
+}
+
+impl Buddy {
+    /// Bundles the files of each `file_bundles` entry into `.buddy/files/`
+    /// and uploads the new or changed bundles to the assistant.
+    /// - `recreate` is `true`, bundles are re-uploaded even if unchanged.
+    /// - Bundles over their `max_size_kb` are uploaded as `-partN` files, and the
+    ///   parts left over from a previous sync are removed from the assistant.
+    /// - Remote bundles no longer configured (or with no files) are removed.
+    pub async fn upload_files(&self, recreate: bool) -> Result<SyncSummary> {
+        let _sync = SYNC_LOCK.lock().await;
+
+        // -- Clean up the bundles of other (previous) assistants.
+        let data_files_dir = self.data_files_sir()?;
+        for entry in fs::read_dir(&data_files_dir)? {
+            let file = entry?.path();
+            if file.is_file() && !file.x_file_name().contains(self.assistant_id.as_str()) {
+                fs::remove_file(&file)?;
+                println!("{} Removed stale bundle '{}'", ico_deleted_ok(), file.x_file_name());
+            }
+        }
+
+        // -- Bundle and upload.
+        let bundles: Vec<&FileBundle> = self.config.file_bundles.iter().collect();
+        let summary = self.sync_bundles(&bundles, recreate, true, true).await?;
+
+        summary.print();
+
+        Ok(summary)
+    }
+
+    /// Builds the `bundles` locally, then uploads what changed with one remote
+    /// listing for the whole sync, and at most `upload_concurrency` uploads in flight.
+    /// - `prune` is `true` when `bundles` are all the configured bundles, so that
+    ///   the remote bundles of none of them can be removed.
+    /// - `show_progress` is `false` for background syncs (watch mode).
+    pub(super) async fn sync_bundles(
+        &self,
+        bundles: &[&FileBundle],
+        recreate: bool,
+        prune: bool,
+        show_progress: bool,
+    ) -> Result<SyncSummary> {
+        // -- Build locally.
+        let data_files_dir = self.data_files_sir()?;
+        let mut local_bundles = Vec::new();
+        for bundle in bundles {
+            if let Some(local_bundle) = self.build_bundle(bundle, &data_files_dir)? {
+                local_bundles.push(local_bundle);
+            }
+        }
+
+        // -- Plan, from a single remote listing.
+        let file_by_name =
+            assistant::get_files_hashmap(&self.open_ai_client, &self.assistant_id).await?;
+        let plan = self.plan_sync(&local_bundles, &file_by_name, recreate, prune)?;
+
+        let uploads: Vec<(usize, &PathBuf)> = plan
+            .bundles
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, b)| b.uploads.iter().map(move |part| (idx, part)))
+            .collect();
+
+        // -- Upload, concurrently.
+        let total_size: u64 = uploads
+            .iter()
+            .filter_map(|(_, part)| fs::metadata(part).ok())
+            .map(|m| m.len())
+            .sum();
+        let progress = if show_progress && !uploads.is_empty() {
+            new_progress_bar(total_size)
+        } else {
+            ProgressBar::hidden()
+        };
+        let num_uploads = uploads.len();
+        let mut num_done = 0;
+        progress.set_message(format!("files 0/{num_uploads}"));
+
+        let concurrency = self.config.upload_concurrency.unwrap_or(DEFAULT_UPLOAD_CONCURRENCY).max(1);
+        let file_by_name_ref = &file_by_name;
+        let mut results = stream::iter(uploads)
+            .map(|(idx, part)| async move {
+                let res = assistant::upload_file_by_name(
+                    &self.open_ai_client,
+                    &self.assistant_id,
+                    part,
+                    file_by_name_ref,
+                    true,
+                )
+                .await;
+                (idx, part, res)
+            })
+            .buffer_unordered(concurrency);
+
+        // Only the remote files still there are worth remembering.
+        let mut uploaded = self.load_uploaded()?;
+        uploaded.retain(|name, _| file_by_name.contains_key(name));
+
+        let mut num_uploaded = vec![0u32; plan.bundles.len()];
+        let mut failures = Vec::new();
+        while let Some((idx, part, res)) = results.next().await {
+            num_done += 1;
+            progress.inc(fs::metadata(part).map(|m| m.len()).unwrap_or(0));
+            progress.set_message(format!("files {num_done}/{num_uploads}"));
+            let name = part.x_file_name().to_string();
+            match res.and_then(|(file_id, _)| Ok((file_id, content_hash(part)?))) {
+                Ok((file_id, hash)) => {
+                    num_uploaded[idx] += 1;
+                    let file_id = file_id.to_string();
+                    uploaded.insert(name, UploadedPart { file_id, hash });
+                }
+                Err(err) => {
+                    // Replaced remote files were deleted before the upload.
+                    uploaded.remove(&name);
+                    failures.push(format!("'{name}': {err}"));
+                }
+            }
+        }
+        drop(results);
+        progress.finish_and_clear();
+
+        // -- Remove the remote parts (or unsplit bundles) not produced this time.
+        let stale = plan.bundles.iter().flat_map(|b| b.stale.iter());
+        for (name, remote) in stale.chain(plan.orphans.iter()) {
+            assistant::delete_file(&self.open_ai_client, &self.assistant_id, &remote.id, name).await;
+            uploaded.remove(name);
+            println!("{} Removed remote bundle file '{}'", ico_deleted_ok(), name);
+        }
+        save_to_json(self.data_sir()?.join(UPLOADED_JSON), &uploaded)?;
+
+        if !failures.is_empty() {
+            for failure in failures.iter() {
+                println!("{} Upload failed {failure}", ico_err());
+            }
+            return Err(format!("{} upload(s) failed", failures.len()).into());
+        }
+
+        let bundles = plan
+            .bundles
+            .into_iter()
+            .zip(num_uploaded)
+            .map(|(bundle_plan, num_uploaded)| BundleSync {
+                bundle_name: bundle_plan.bundle_name,
+                num_files: bundle_plan.num_files,
+                num_parts: bundle_plan.num_parts,
+                size: bundle_plan.size,
+                num_uploaded,
+                skipped: bundle_plan.skipped,
+            })
+            .collect();
+
+        Ok(SyncSummary { bundles })
+    }
+
+    /// See `LocalBundle::plan`. With `prune`, the remote bundle files owned by
+    /// none of `local_bundles` are planned for removal.
+    pub(super) fn plan_sync(
+        &self,
+        local_bundles: &[LocalBundle],
+        file_by_name: &HashMap<String, RemoteFile>,
+        recreate: bool,
+        prune: bool,
+    ) -> Result<SyncPlan> {
+        let mut plan = SyncPlan::default();
+        for local_bundle in local_bundles {
+            plan.bundles.push(local_bundle.plan(file_by_name, recreate)?);
+        }
+
+        if prune {
+            let mut orphans: Vec<(String, RemoteFile)> = file_by_name
+                .iter()
+                .filter(|(name, _)| self.bundle_name_of(name).is_some())
+                .filter(|(name, _)| !local_bundles.iter().any(|b| b.owns_remote(name)))
+                .map(|(name, remote)| (name.clone(), remote.clone()))
+                .collect();
+            orphans.sort_by(|a, b| a.0.cmp(&b.0));
+            plan.orphans = orphans;
+        }
+
+        Ok(plan)
+    }
+
+    /// Bundles the files of `bundle` into `out_dir`, with what was uploaded of it
+    /// (compared against by `LocalBundle::plan`).
+    /// Returns `None` when its `src_dir` does not exist or has no matching files.
+    pub(super) fn build_bundle(&self, bundle: &FileBundle, out_dir: &Path) -> Result<Option<LocalBundle>> {
+        let src_dir = self.dir.join(&bundle.src_dir);
+        if !src_dir.is_dir() {
+            return Ok(None);
+        }
+
+        let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
+        let exclude_globs: Vec<&str> = bundle.exclude_globs.iter().map(AsRef::as_ref).collect();
+        let files = list_files(&src_dir, Some(&src_globs), Some(&exclude_globs))?;
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let bundle_file = out_dir.join(self.bundle_file_name(bundle));
+        let report = bundle_to_files(
+            files,
+            &bundle_file,
+            bundle.limits(),
+            &bundle.bundle_format(&self.project_root()),
+        )?;
+
+        let mut local_bundle = LocalBundle {
+            bundle_name: bundle.bundle_name.clone(),
+            bundle_file,
+            uploaded: HashMap::new(),
+            report,
+        };
+        local_bundle.uploaded = self
+            .load_uploaded()?
+            .into_iter()
+            .filter(|(name, _)| local_bundle.owns_remote(name))
+            .collect();
+
+        Ok(Some(local_bundle))
+    }
+
+    /// The bundle files uploaded by the previous syncs, by file name.
+    /// Does not create the data dir (see `sync --dry-run`).
+    fn load_uploaded(&self) -> Result<HashMap<String, UploadedPart>> {
+        let file = self.dir.join(DATA_DIR).join(UPLOADED_JSON);
+        if !file.is_file() {
+            return Ok(HashMap::new());
+        }
+        load_from_json(file)
+    }
+
+    fn bundle_file_name(&self, bundle: &FileBundle) -> String {
+        format!(
+            "{}-{}-bundle-{}.{}",
+            self.name(),
+            bundle.bundle_name,
+            self.assistant_id,
+            bundle.dst_ext
+        )
+    }
+
+    /// The bundle name of a remote bundle file of this buddy (see `bundle_file_name`).
+    pub(super) fn bundle_name_of<'a>(&self, file_name: &'a str) -> Option<&'a str> {
+        let rest = file_name.strip_prefix(self.name())?.strip_prefix('-')?;
+        let (bundle_name, _) = rest.split_once(&format!("-bundle-{}", self.assistant_id))?;
+        Some(bundle_name)
+    }
+}
+
+/// FNV-1a hash of the content of `file`, stable across builds as it is stored.
+fn content_hash(file: &Path) -> Result<u64> {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let hash = fs::read(file)?
+        .iter()
+        .fold(FNV_OFFSET, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME));
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ais::assistant::FileId;
+
+    fn local_bundle(dir: &Path, parts: &[(&str, &str)]) -> Result<LocalBundle> {
+        let mut report = BundleReport::default();
+        for (name, content) in parts {
+            let part = dir.join(name);
+            fs::write(&part, content)?;
+            report.parts.push(part);
+        }
+        Ok(LocalBundle {
+            bundle_name: "docs".to_string(),
+            bundle_file: dir.join("b-docs-bundle-a1.md"),
+            uploaded: HashMap::new(),
+            report,
+        })
+    }
+
+    fn remote(name: &str, bytes: u64) -> (String, RemoteFile) {
+        let id = FileId::from(format!("file-{name}"));
+        (name.to_string(), RemoteFile { id, bytes })
+    }
+
+    #[test]
+    fn test_plan_create_and_unchanged() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut bundle = local_bundle(dir.path(), &[("b-docs-bundle-a1.md", "hello")])?;
+
+        let plan = bundle.plan(&HashMap::new(), false)?;
+        assert_eq!(plan.action(), BundleAction::Create);
+        assert_eq!(plan.uploads.len(), 1);
+
+        // Same content as the one uploaded, and the remote file is still that upload.
+        let uploaded = UploadedPart {
+            file_id: "file-b-docs-bundle-a1.md".to_string(),
+            hash: content_hash(&bundle.report.parts[0])?,
+        };
+        bundle.uploaded.insert("b-docs-bundle-a1.md".to_string(), uploaded.clone());
+        let file_by_name = HashMap::from([remote("b-docs-bundle-a1.md", 5)]);
+        let plan = bundle.plan(&file_by_name, false)?;
+        assert_eq!(plan.action(), BundleAction::Unchanged);
+
+        // Unknown upload, or another remote file.
+        bundle.uploaded.clear();
+        assert_eq!(bundle.plan(&file_by_name, false)?.action(), BundleAction::Update);
+        let other = UploadedPart {
+            file_id: "file-other".to_string(),
+            ..uploaded
+        };
+        bundle.uploaded.insert("b-docs-bundle-a1.md".to_string(), other);
+        assert_eq!(bundle.plan(&file_by_name, false)?.action(), BundleAction::Update);
+
+        assert_eq!(bundle.plan(&file_by_name, true)?.action(), BundleAction::Update);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_update_changed_and_stale_parts() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut bundle = local_bundle(dir.path(), &[("b-docs-bundle-a1.md", "hello!")])?;
+        let uploaded = UploadedPart {
+            file_id: "file-b-docs-bundle-a1.md".to_string(),
+            hash: 0,
+        };
+        bundle.uploaded.insert("b-docs-bundle-a1.md".to_string(), uploaded);
+        let file_by_name = HashMap::from([
+            remote("b-docs-bundle-a1.md", 6),
+            remote("b-docs-bundle-a1-part2.md", 10),
+            remote("b-other-bundle-a1.md", 10),
+        ]);
+
+        let plan = bundle.plan(&file_by_name, false)?;
+
+        assert_eq!(plan.action(), BundleAction::Update);
+        assert_eq!(plan.uploads.len(), 1);
+        let stale: Vec<&str> = plan.stale.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(stale, ["b-docs-bundle-a1-part2.md"]);
+        assert_eq!((plan.num_remote, plan.remote_size), (2, 16));
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+use crate::ais::assistant::{self, ThreadId, ThreadMsg};
+use crate::ais::OaClient;
+use crate::buddy::{Buddy, Conv, LayeredConfig, CONV_JSON, DATA_DIR};
+use crate::utils::files::{ensure_dir, load_from_json, read_to_string};
+use crate::Result;
+use async_openai::types::MessageRole;
+use chrono::{DateTime, Local, SecondsFormat};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const TRANSCRIPTS_DIR: &str = "transcripts";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[display("User")]
+    User,
+    #[display("Assistant")]
+    Assistant,
+}
+
+/// A line of a transcript: a user message or an assistant reply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// RFC 3339, local time.
+    pub at: String,
+    pub role: Role,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Paths of the files attached to a user message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    /// Ids of the attachments uploaded as message files, deleted with the
+    /// conversation (see `Buddy::release_conv_files`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_ids: Vec<String>,
+}
+
+impl TranscriptEntry {
+    pub fn user(text: &str, attachments: Vec<String>) -> Self {
+        Self {
+            at: now(),
+            role: Role::User,
+            text: text.to_string(),
+            run_id: None,
+            model: None,
+            attachments,
+            file_ids: Vec::new(),
+        }
+    }
+
+    pub fn assistant(text: &str, run_id: &str, model: &str) -> Self {
+        Self {
+            at: now(),
+            role: Role::Assistant,
+            text: text.to_string(),
+            run_id: Some(run_id.to_string()),
+            model: Some(model.to_string()),
+            attachments: Vec::new(),
+            file_ids: Vec::new(),
+        }
+    }
+}
+
+/// From the thread messages, which have no model.
+impl From<&ThreadMsg> for TranscriptEntry {
+    fn from(msg: &ThreadMsg) -> Self {
+        Self {
+            at: DateTime::from_timestamp(msg.created_at, 0)
+                .map(|at| at.with_timezone(&Local).to_rfc3339_opts(SecondsFormat::Secs, false))
+                .unwrap_or_default(),
+            role: match msg.role {
+                MessageRole::User => Role::User,
+                MessageRole::Assistant => Role::Assistant,
+            },
+            text: msg.text.clone(),
+            run_id: msg.run_id.clone(),
+            model: None,
+            attachments: Vec::new(),
+            file_ids: msg.file_ids.clone(),
+        }
+    }
+}
+
+/// The local log of a conversation, `.buddy/transcripts/<thread_id>.jsonl`,
+/// one `TranscriptEntry` per line.
+#[derive(Debug)]
+pub struct Transcript {
+    file: PathBuf,
+}
+
+impl Transcript {
+    fn of_thread(dir: &Path, thread_id: &ThreadId) -> Self {
+        let file = dir.join(DATA_DIR).join(TRANSCRIPTS_DIR).join(format!("{thread_id}.jsonl"));
+        Self { file }
+    }
+
+    pub fn append(&self, entry: &TranscriptEntry) -> Result<()> {
+        if let Some(dir) = self.file.parent() {
+            ensure_dir(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.file)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// All the entries, oldest first (none if the file does not exist yet).
+    pub fn entries(&self) -> Result<Vec<TranscriptEntry>> {
+        if !self.file.exists() {
+            return Ok(Vec::new());
+        }
+        read_to_string(&self.file)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line)
+                    .map_err(|err| format!("{}:{}: {err}", self.file.display(), idx + 1).into())
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum ExportFormat {
+    #[display("md")]
+    Md,
+    #[display("json")]
+    Json,
+    #[display("html")]
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "md" | "markdown" => Ok(Self::Md),
+            "json" => Ok(Self::Json),
+            "html" => Ok(Self::Html),
+            other => Err(format!("Expected 'md', 'json' or 'html', got '{other}'")),
+        }
+    }
+}
+
+/// Renders the `entries` of the conversation of `title` in `format`.
+pub fn export_transcript(title: &str, entries: &[TranscriptEntry], format: ExportFormat) -> Result<String> {
+    let out = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(entries)?,
+        ExportFormat::Md => {
+            let mut out = format!("# {title}\n");
+            for entry in entries {
+                out.push_str(&format!("\n### {} · {}\n", entry.role, entry.at));
+                if let Some(meta) = entry_meta(entry) {
+                    out.push_str(&format!("\n_{meta}_\n"));
+                }
+                out.push_str(&format!("\n{}\n", entry.text.trim_end()));
+            }
+            out
+        }
+        ExportFormat::Html => {
+            let mut out = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+                 <style>body {{ font-family: sans-serif; max-width: 50em; margin: auto; }} \
+                 .user {{ background: #eef4ff; }} .assistant {{ background: #f6f6f6; }} \
+                 .msg {{ padding: 0.5em 1em; margin: 1em 0; border-radius: 6px; }} \
+                 .meta {{ color: #777; font-size: 0.85em; }} \
+                 pre {{ white-space: pre-wrap; font-family: inherit; }}</style>\n\
+                 </head>\n<body>\n<h1>{0}</h1>\n",
+                html_escape(title)
+            );
+            for entry in entries {
+                let class = match entry.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                };
+                let mut meta = format!("{} · {}", entry.role, entry.at);
+                if let Some(more) = entry_meta(entry) {
+                    meta.push_str(&format!(" · {more}"));
+                }
+                out.push_str(&format!(
+                    "<div class=\"msg {class}\">\n<div class=\"meta\">{}</div>\n<pre>{}</pre>\n</div>\n",
+                    html_escape(&meta),
+                    html_escape(entry.text.trim_end())
+                ));
+            }
+            out.push_str("</body>\n</html>\n");
+            out
+        }
+    };
+
+    Ok(out)
+}
+
+/// Model, run id and attachments, if any.
+fn entry_meta(entry: &TranscriptEntry) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(model) = entry.model.as_ref() {
+        parts.push(model.clone());
+    }
+    if let Some(run_id) = entry.run_id.as_ref() {
+        parts.push(run_id.clone());
+    }
+    if !entry.attachments.is_empty() {
+        parts.push(format!("attached: {}", entry.attachments.join(", ")));
+    }
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn now() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+impl Buddy {
+    pub fn transcript(&self, conv: &Conv) -> Transcript {
+        Transcript::of_thread(&self.dir, &conv.thread_id)
+    }
+
+    /// A page of `limit` messages of `conv`, newest first, with the cursor of
+    /// the next (older) page (see `assistant::list_thread_msgs_page`).
+    pub async fn history_page(
+        &self,
+        conv: &Conv,
+        limit: usize,
+        cursor: Option<String>,
+    ) -> Result<(Vec<ThreadMsg>, Option<String>)> {
+        assistant::list_thread_msgs_page(&self.open_ai_client, &conv.thread_id, limit, cursor).await
+    }
+
+    /// The entries of the thread messages `msgs` of `conv`: the local transcript
+    /// when it has all of them (with the models and the messages as typed),
+    /// the messages converted otherwise.
+    pub(super) fn entries_for(&self, conv: &Conv, msgs: &[ThreadMsg]) -> Result<Vec<TranscriptEntry>> {
+        let entries = self.transcript(conv).entries()?;
+        if entries.len() == msgs.len() {
+            Ok(entries)
+        } else {
+            Ok(msgs.iter().map(TranscriptEntry::from).collect())
+        }
+    }
+
+    /// The full history of `conv` from the API.
+    pub async fn remote_transcript(&self, conv: &Conv) -> Result<Vec<TranscriptEntry>> {
+        remote_entries(&self.open_ai_client, &conv.thread_id).await
+    }
+}
+
+/// `export`: exports the current conversation of the buddy `dir` to `out`,
+/// from the local transcript, or from the API with `remote`.
+/// Neither the assistant nor the conversation are created.
+/// Returns the number of messages exported.
+pub async fn export_conv(dir: &Path, format: ExportFormat, out: &Path, remote: bool) -> Result<usize> {
+    let conv_file = dir.join(DATA_DIR).join(CONV_JSON);
+    let conv: Conv = load_from_json(&conv_file)
+        .map_err(|_| format!("No conversation for the buddy '{}' yet", dir.display()))?;
+
+    let config = LayeredConfig::load(dir)?.config;
+    let entries = if remote {
+        remote_entries(&config.open_ai_client(dir)?, &conv.thread_id).await?
+    } else {
+        Transcript::of_thread(dir, &conv.thread_id).entries()?
+    };
+
+    let title = format!("{} · {}", config.name, conv.thread_id);
+    fs::write(out, export_transcript(&title, &entries, format)?)?;
+
+    Ok(entries.len())
+}
+
+/// The messages of the thread as entries.
+async fn remote_entries(oac: &OaClient, thread_id: &ThreadId) -> Result<Vec<TranscriptEntry>> {
+    let msgs = assistant::list_thread_msgs(oac, thread_id).await?;
+    Ok(msgs.iter().map(TranscriptEntry::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_append_and_entries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let transcript = Transcript::of_thread(dir.path(), &ThreadId::from("thread_1".to_string()));
+        assert!(transcript.entries()?.is_empty());
+
+        let mut user = TranscriptEntry::user("Hi", vec!["src/main.rs".to_string()]);
+        user.file_ids = vec!["file-1".to_string()];
+        let reply = TranscriptEntry::assistant("Hello", "run_1", "gpt-4o");
+        transcript.append(&user)?;
+        transcript.append(&reply)?;
+
+        assert_eq!(transcript.entries()?, vec![user, reply]);
+        assert!(dir.path().join(".buddy/transcripts/thread_1.jsonl").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_md_and_html() -> Result<()> {
+        let mut user = TranscriptEntry::user("Is <T> & 'a ok?", Vec::new());
+        user.at = "2024-05-01T10:00:00+02:00".to_string();
+        let mut reply = TranscriptEntry::assistant("Yes.\n", "run_1", "gpt-4o");
+        reply.at = "2024-05-01T10:00:05+02:00".to_string();
+        let entries = [user, reply];
+
+        let md = export_transcript("buddy", &entries, ExportFormat::Md)?;
+        assert_eq!(
+            md,
+            "# buddy\n\n### User · 2024-05-01T10:00:00+02:00\n\nIs <T> & 'a ok?\n\n\
+             ### Assistant · 2024-05-01T10:00:05+02:00\n\n_gpt-4o · run_1_\n\nYes.\n"
+        );
+
+        let html = export_transcript("buddy", &entries, ExportFormat::Html)?;
+        assert!(html.contains("<pre>Is &lt;T&gt; &amp; 'a ok?</pre>"), "{html}");
+        assert!("pdf".parse::<ExportFormat>().is_err());
+        Ok(())
+    }
+}
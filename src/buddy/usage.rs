@@ -0,0 +1,177 @@
+use crate::buddy::config::ModelPrice;
+use crate::utils::files::{load_from_json, save_to_json};
+use crate::Result;
+use async_openai::types::RunCompletionUsage;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::path::Path;
+
+/// Token counts and cost, for one run or aggregated over many.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub runs: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// USD. Runs of models without a configured price count as 0.
+    pub cost: f64,
+}
+
+impl TokenUsage {
+    pub fn from_run(usage: &RunCompletionUsage, price: Option<ModelPrice>) -> Self {
+        let prompt_tokens = usage.prompt_tokens as u64;
+        let completion_tokens = usage.completion_tokens as u64;
+        let cost = price
+            .map(|p| {
+                (prompt_tokens as f64 * p.prompt + completion_tokens as f64 * p.completion)
+                    / 1_000_000.
+            })
+            .unwrap_or(0.);
+
+        TokenUsage {
+            runs: 1,
+            prompt_tokens,
+            completion_tokens,
+            cost,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.runs += other.runs;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// Persistent usage log (`.buddy/usage.json`), aggregated by day then by model.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageLog {
+    days: BTreeMap<String, BTreeMap<String, TokenUsage>>,
+}
+
+impl UsageLog {
+    /// Loads the log, or starts an empty one if the file does not exist yet.
+    pub fn load(file: &Path) -> Result<Self> {
+        if file.exists() {
+            load_from_json(file)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, file: &Path) -> Result<()> {
+        save_to_json(file, self)
+    }
+
+    /// Adds a run to today's entry of `model`.
+    pub fn record(&mut self, model: &str, usage: TokenUsage) {
+        *self
+            .days
+            .entry(today())
+            .or_default()
+            .entry(model.to_string())
+            .or_default() += usage;
+    }
+
+    /// Usage per model over the days starting with `day_prefix`
+    /// (`2024-05-21` for a day, `2024-05` for a month).
+    pub fn by_model(&self, day_prefix: &str) -> BTreeMap<String, TokenUsage> {
+        let mut res: BTreeMap<String, TokenUsage> = BTreeMap::new();
+        for (_, models) in self.days.iter().filter(|(d, _)| d.starts_with(day_prefix)) {
+            for (model, usage) in models {
+                *res.entry(model.clone()).or_default() += *usage;
+            }
+        }
+        res
+    }
+
+    pub fn total(&self, day_prefix: &str) -> TokenUsage {
+        let mut total = TokenUsage::default();
+        for usage in self.by_model(day_prefix).into_values() {
+            total += usage;
+        }
+        total
+    }
+}
+
+/// e.g. `2024-05-21`
+pub fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// e.g. `2024-05`
+pub fn this_month() -> String {
+    Local::now().format("%Y-%m").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_usage(prompt_tokens: u32, completion_tokens: u32) -> RunCompletionUsage {
+        RunCompletionUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    #[test]
+    fn test_usage_from_run_prices() {
+        let price = ModelPrice {
+            prompt: 5.0,
+            completion: 15.0,
+        };
+
+        // -- Price per 1M tokens
+        let usage = TokenUsage::from_run(&run_usage(200_000, 100_000), Some(price));
+        assert_eq!((usage.runs, usage.total_tokens()), (1, 300_000));
+        assert!((usage.cost - 2.5).abs() < 1e-9, "{}", usage.cost);
+
+        // -- No price
+        let usage = TokenUsage::from_run(&run_usage(200_000, 100_000), None);
+        assert_eq!(usage.total_tokens(), 300_000);
+        assert_eq!(usage.cost, 0.);
+    }
+
+    #[test]
+    fn test_usage_log_by_model_and_month() {
+        let price = Some(ModelPrice {
+            prompt: 1.0,
+            completion: 2.0,
+        });
+        let mut log = UsageLog::default();
+        let mut add = |day: &str, model: &str, usage: TokenUsage| {
+            *log.days.entry(day.to_string()).or_default().entry(model.to_string()).or_default() += usage;
+        };
+        add("2024-05-20", "gpt-4o", TokenUsage::from_run(&run_usage(1_000_000, 0), price));
+        add("2024-05-21", "gpt-4o", TokenUsage::from_run(&run_usage(0, 1_000_000), price));
+        add("2024-05-21", "gpt-4o-mini", TokenUsage::from_run(&run_usage(500, 500), None));
+        add("2024-06-01", "gpt-4o", TokenUsage::from_run(&run_usage(1_000_000, 0), price));
+
+        // -- Month, across two days and two models
+        let by_model = log.by_model("2024-05");
+        assert_eq!(by_model.keys().collect::<Vec<_>>(), ["gpt-4o", "gpt-4o-mini"]);
+        let gpt_4o = by_model["gpt-4o"];
+        assert_eq!((gpt_4o.runs, gpt_4o.prompt_tokens, gpt_4o.completion_tokens), (2, 1_000_000, 1_000_000));
+        let total = log.total("2024-05");
+        assert_eq!((total.runs, total.total_tokens()), (3, 2_001_000));
+        assert!((total.cost - 3.0).abs() < 1e-9, "{}", total.cost);
+
+        // -- Day
+        assert_eq!(log.total("2024-05-21").runs, 2);
+        assert_eq!(log.total("2024-04").runs, 0);
+
+        // -- `record` adds to today
+        log.record("gpt-4o", TokenUsage::from_run(&run_usage(10, 10), price));
+        assert_eq!(log.total(&today()).total_tokens(), 20);
+    }
+}
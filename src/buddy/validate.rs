@@ -0,0 +1,605 @@
+use crate::buddy::config::Config;
+use crate::buddy::layers::{display_key, ConfigLayers, ConfigSource};
+use crate::utils::cli::{ico_err, ico_warn};
+use crate::utils::files::{get_glob_set, list_files};
+use derive_more::Display;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum Severity {
+    #[display("error")]
+    Error,
+    #[display("warning")]
+    Warning,
+}
+
+/// A config mistake, located at the file and line (or env variable) that set the value.
+#[derive(Debug)]
+pub struct ConfigProblem {
+    pub severity: Severity,
+    pub location: String,
+    pub key: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ico = match self.severity {
+            Severity::Error => ico_err(),
+            Severity::Warning => ico_warn(),
+        };
+        write!(f, "{ico} {}: `{}`: {}", self.location, self.key, self.message)?;
+        if let Some(suggestion) = self.suggestion.as_ref() {
+            write!(f, "\n    help: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+// Schema of `Config`, to report all the unknown keys, wrong types and
+// missing fields at once (serde stops at the first one, without location).
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Str,
+    /// Non negative integer.
+    Int,
+    /// Float or integer.
+    Float,
+    Bool,
+    Strs,
+    Table(&'static [Field]),
+    /// Array of tables.
+    Tables(&'static [Field]),
+    /// Table of tables with any keys (e.g., `prices."gpt-4o"`).
+    Map(&'static [Field]),
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Str => "a string",
+            Kind::Int => "a positive integer",
+            Kind::Float => "a number",
+            Kind::Bool => "true or false",
+            Kind::Strs => "an array of strings",
+            Kind::Table(_) | Kind::Map(_) => "a table",
+            Kind::Tables(_) => "an array of tables",
+        }
+    }
+}
+
+struct Field {
+    name: &'static str,
+    kind: Kind,
+    required: bool,
+}
+
+const fn req(name: &'static str, kind: Kind) -> Field {
+    Field { name, kind, required: true }
+}
+
+const fn opt(name: &'static str, kind: Kind) -> Field {
+    Field { name, kind, required: false }
+}
+
+const FORMAT_FIELDS: &[Field] = &[
+    opt("comment", Kind::Str),
+    opt("toc", Kind::Bool),
+    opt("line_numbers", Kind::Bool),
+    opt("file_meta", Kind::Bool),
+];
+
+const BUNDLE_FIELDS: &[Field] = &[
+    req("bundle_name", Kind::Str),
+    req("src_dir", Kind::Str),
+    req("dst_ext", Kind::Str),
+    req("src_globs", Kind::Strs),
+    opt("exclude_globs", Kind::Strs),
+    opt("max_size_kb", Kind::Int),
+    opt("max_file_size_kb", Kind::Int),
+    opt("format", Kind::Table(FORMAT_FIELDS)),
+];
+
+const PRICE_FIELDS: &[Field] = &[req("prompt", Kind::Float), req("completion", Kind::Float)];
+
+const OUTPUT_FIELDS: &[Field] = &[
+    opt("wrap_width", Kind::Int),
+    opt("color", Kind::Bool),
+    opt("show_usage", Kind::Bool),
+    opt("history_on_load", Kind::Int),
+    opt("verbose_steps", Kind::Bool),
+];
+
+const RETRY_FIELDS: &[Field] = &[
+    opt("max_elapsed_secs", Kind::Int),
+    opt("initial_interval_ms", Kind::Int),
+];
+
+const COMPACT_FIELDS: &[Field] = &[
+    opt("threshold_tokens", Kind::Int),
+    opt("keep_turns", Kind::Int),
+];
+
+const CONFIG_FIELDS: &[Field] = &[
+    opt("name", Kind::Str),
+    opt("model", Kind::Str),
+    opt("instructions_file", Kind::Str),
+    opt("api_base", Kind::Str),
+    opt("api_key_env", Kind::Str),
+    opt("api_key_file", Kind::Str),
+    opt("api_key_cmd", Kind::Str),
+    opt("project_root", Kind::Str),
+    opt("file_bundles", Kind::Tables(BUNDLE_FIELDS)),
+    opt("prices", Kind::Map(PRICE_FIELDS)),
+    opt("monthly_budget", Kind::Float),
+    opt("upload_concurrency", Kind::Int),
+    opt("watch", Kind::Bool),
+    opt("output", Kind::Table(OUTPUT_FIELDS)),
+    opt("retry", Kind::Table(RETRY_FIELDS)),
+    opt("compact", Kind::Table(COMPACT_FIELDS)),
+];
+
+/// Whether the config key at `path` is a string (e.g., `model`), `None` if it is not in the schema.
+/// `path` goes through arrays of tables with indexes, and maps with their keys.
+pub(super) fn is_string_key(path: &[String]) -> Option<bool> {
+    let mut fields = CONFIG_FIELDS;
+    let mut path = path.iter();
+    while let Some(name) = path.next() {
+        let field = fields.iter().find(|f| f.name == name)?;
+        fields = match field.kind {
+            Kind::Str => return path.next().is_none().then_some(true),
+            Kind::Table(fields) => fields,
+            Kind::Tables(fields) | Kind::Map(fields) => {
+                path.next()?;
+                fields
+            }
+            _ => return path.next().is_none().then_some(false),
+        };
+    }
+    // A table
+    Some(false)
+}
+
+/// Validates the merged config of the buddy `dir`, and deserializes it when
+/// its structure is valid. Returns all the problems found.
+pub(super) fn validate(layers: &ConfigLayers, dir: &Path) -> (Option<Config>, Vec<ConfigProblem>) {
+    let mut checker = Checker {
+        layers,
+        problems: Vec::new(),
+    };
+
+    // -- Interpolation
+    for (path, name) in layers.unresolved.iter() {
+        checker.error(
+            path,
+            format!("env variable '{name}' is not set"),
+            Some(format!("set it (e.g., in `.env`), or give a default with `${{{name}:-default}}`")),
+        );
+    }
+
+    // -- Structure
+    checker.check_table(&layers.merged, CONFIG_FIELDS, &mut Vec::new());
+    sort_by_location(&mut checker.problems);
+    if checker.has_errors() {
+        return (None, checker.problems);
+    }
+
+    let config: Config = match Value::Table(layers.merged.clone()).try_into() {
+        Ok(config) => config,
+        Err(err) => {
+            checker.error(&[], err.to_string().trim().replace('\n', " "), None);
+            return (None, checker.problems);
+        }
+    };
+
+    // -- Semantic
+    checker.check_config(&config, dir);
+
+    (Some(config), checker.problems)
+}
+
+/// In file then line order (the merged tables are sorted by key).
+fn sort_by_location(problems: &mut [ConfigProblem]) {
+    problems.sort_by_cached_key(|p| {
+        let file_line = p.location.rsplit_once(':');
+        match file_line.and_then(|(file, line)| Some((file, line.parse::<usize>().ok()?))) {
+            Some((file, line)) => (file.to_string(), line),
+            None => (p.location.clone(), 0),
+        }
+    });
+}
+
+struct Checker<'a> {
+    layers: &'a ConfigLayers,
+    problems: Vec<ConfigProblem>,
+}
+
+impl Checker<'_> {
+    fn has_errors(&self) -> bool {
+        self.problems.iter().any(|p| p.severity == Severity::Error)
+    }
+
+    fn push(&mut self, severity: Severity, path: &[String], message: String, suggestion: Option<String>) {
+        self.problems.push(ConfigProblem {
+            severity,
+            location: self.layers.location_of(path),
+            key: display_key(path),
+            message,
+            suggestion,
+        });
+    }
+
+    fn error(&mut self, path: &[String], message: String, suggestion: Option<String>) {
+        self.push(Severity::Error, path, message, suggestion);
+    }
+
+    fn warning(&mut self, path: &[String], message: String, suggestion: Option<String>) {
+        self.push(Severity::Warning, path, message, suggestion);
+    }
+
+    fn check_table(&mut self, table: &Table, fields: &[Field], path: &mut Vec<String>) {
+        for (key, value) in table {
+            path.push(key.clone());
+            match fields.iter().find(|f| f.name == key) {
+                Some(field) => self.check_value(value, field.kind, path),
+                None => {
+                    let suggestion = closest(key, fields.iter().map(|f| f.name))
+                        .map(|name| format!("did you mean `{name}`?"));
+                    // Env variables of other tools can share the prefix.
+                    let severity = match self.layers.source_of(path) {
+                        ConfigSource::Env(_) => Severity::Warning,
+                        _ => Severity::Error,
+                    };
+                    self.push(severity, path, "unknown key".to_string(), suggestion);
+                }
+            }
+            path.pop();
+        }
+
+        for field in fields.iter().filter(|f| f.required && !table.contains_key(f.name)) {
+            path.push(field.name.to_string());
+            self.error(path, format!("missing, expected {}", field.kind.name()), None);
+            path.pop();
+        }
+    }
+
+    fn check_value(&mut self, value: &Value, kind: Kind, path: &mut Vec<String>) {
+        match (kind, value) {
+            (Kind::Str, Value::String(_))
+            | (Kind::Bool, Value::Boolean(_))
+            | (Kind::Float, Value::Float(_) | Value::Integer(_)) => (),
+            (Kind::Int, Value::Integer(i)) if *i >= 0 => (),
+            (Kind::Strs, Value::Array(items)) if items.iter().all(Value::is_str) => (),
+            (Kind::Table(fields), Value::Table(table)) => self.check_table(table, fields, path),
+            (Kind::Tables(fields), Value::Array(items)) if items.iter().all(Value::is_table) => {
+                for (idx, item) in items.iter().enumerate() {
+                    path.push(idx.to_string());
+                    if let Value::Table(table) = item {
+                        self.check_table(table, fields, path);
+                    }
+                    path.pop();
+                }
+            }
+            (Kind::Map(fields), Value::Table(table)) => {
+                for (key, item) in table {
+                    path.push(key.clone());
+                    self.check_value(item, Kind::Table(fields), path);
+                    path.pop();
+                }
+            }
+            (kind, value) => {
+                let message = format!("expected {}, found {}", kind.name(), value_name(value));
+                self.error(path, message, None);
+            }
+        }
+    }
+
+    fn check_config(&mut self, config: &Config, dir: &Path) {
+        let key = |key: &str| -> Vec<String> { key.split('.').map(String::from).collect() };
+
+        // -- Paths
+        if !dir.join(&config.instructions_file).is_file() {
+            self.warning(
+                &key("instructions_file"),
+                format!("file '{}' not found, the assistant will run without instructions", config.instructions_file),
+                Some("create it, or set `instructions_file` (relative to the buddy dir)".to_string()),
+            );
+        }
+        if let Some(project_root) = config.project_root.as_ref() {
+            if !dir.join(project_root).is_dir() {
+                self.error(&key("project_root"), format!("directory '{project_root}' not found"), None);
+            }
+        }
+        let key_sources = [
+            ("api_key_env", config.api_key_env.is_some()),
+            ("api_key_file", config.api_key_file.is_some()),
+            ("api_key_cmd", config.api_key_cmd.is_some()),
+        ];
+        let key_sources: Vec<&str> = key_sources.iter().filter(|(_, set)| *set).map(|(k, _)| *k).collect();
+        if let [_, others @ ..] = key_sources.as_slice() {
+            for other in others {
+                self.error(
+                    &key(other),
+                    format!("only one API key source can be set, `{}` is set too", key_sources[0]),
+                    None,
+                );
+            }
+        }
+        if config.upload_concurrency == Some(0) {
+            self.warning(&key("upload_concurrency"), "0 uploads at a time, 1 is used".to_string(), None);
+        }
+
+        // -- Bundles
+        for (idx, bundle) in config.file_bundles.iter().enumerate() {
+            let bundle_key = |field: &str| -> Vec<String> {
+                vec!["file_bundles".to_string(), idx.to_string(), field.to_string()]
+            };
+
+            let first = config.file_bundles[..idx].iter().position(|b| b.bundle_name == bundle.bundle_name);
+            if let Some(first) = first {
+                let first_path = vec!["file_bundles".to_string(), first.to_string(), "bundle_name".to_string()];
+                self.error(
+                    &bundle_key("bundle_name"),
+                    format!("duplicate bundle name '{}'", bundle.bundle_name),
+                    Some(format!("also defined at {}", self.layers.location_of(&first_path))),
+                );
+            }
+
+            if bundle.dst_ext.is_empty() {
+                self.error(&bundle_key("dst_ext"), "empty extension".to_string(), None);
+            }
+
+            let mut globs_ok = true;
+            for (field, globs) in [("src_globs", &bundle.src_globs), ("exclude_globs", &bundle.exclude_globs)] {
+                for glob in globs {
+                    if let Err(err) = get_glob_set(&[glob.as_str()]) {
+                        self.error(&bundle_key(field), format!("invalid glob '{glob}': {err}"), None);
+                        globs_ok = false;
+                    }
+                }
+            }
+
+            let src_dir = dir.join(&bundle.src_dir);
+            if !src_dir.is_dir() {
+                let suggestion = closest_dir(&src_dir).map(|d| format!("did you mean '{d}'?"));
+                self.warning(
+                    &bundle_key("src_dir"),
+                    format!("directory '{}' not found (relative to the buddy dir), the bundle is skipped", bundle.src_dir),
+                    suggestion,
+                );
+            } else if globs_ok {
+                let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
+                let exclude_globs: Vec<&str> = bundle.exclude_globs.iter().map(AsRef::as_ref).collect();
+                let files = list_files(&src_dir, Some(&src_globs), Some(&exclude_globs));
+                if files.map(|f| f.is_empty()).unwrap_or(false) {
+                    self.warning(
+                        &bundle_key("src_globs"),
+                        format!("no files match in '{}'", bundle.src_dir),
+                        Some("globs are relative to `src_dir`, use `**/` to match at any depth".to_string()),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn value_name(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "a string",
+        Value::Integer(_) => "an integer",
+        Value::Float(_) => "a float",
+        Value::Boolean(_) => "a boolean",
+        Value::Datetime(_) => "a datetime",
+        Value::Array(_) => "an array",
+        Value::Table(_) => "a table",
+    }
+}
+
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    const MAX_DISTANCE: usize = 3;
+
+    candidates
+        .map(|c| (strsim::levenshtein(name, c), c))
+        .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// The closest existing sibling of the missing `dir`, as a path like `dir`.
+fn closest_dir(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_str()?;
+    let parent = dir.parent()?;
+    let siblings: Vec<String> = fs::read_dir(parent)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(String::from))
+        .collect();
+    let sibling = closest(name, siblings.iter().map(String::as_str))?;
+    Some(dir.with_file_name(sibling).display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, Kind, Severity, CONFIG_FIELDS};
+    use crate::buddy::config::{
+        BundleFormatConfig, CompactConfig, Config, FileBundle, ModelPrice, OutputConfig, RetryConfig,
+    };
+    use crate::buddy::layers::LayeredConfig;
+    use crate::buddy::BUDDY_TOML;
+    use crate::Result;
+    use std::collections::HashMap;
+    use std::fs;
+    use toml::{Table, Value};
+
+    #[test]
+    fn test_validate_reports_all_problems() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("src"))?;
+        fs::write(dir.path().join("instructions.md"), "hi")?;
+        fs::write(
+            dir.path().join(BUDDY_TOML),
+            r#"name = "b"
+modle = "gpt-4o"
+
+[output]
+wrap_width = "wide"
+
+[[file_bundles]]
+bundle_name = "code"
+src_dir = "srcc"
+src_globs = ["**/*.rs"]
+dst_ext = "rs"
+
+[[file_bundles]]
+bundle_name = "docs"
+src_dir = "src"
+src_globs = ["*.md"]
+"#,
+        )?;
+
+        let (config, problems) = LayeredConfig::check_from(None, dir.path(), std::iter::empty())?;
+
+        assert!(config.is_none());
+        let problems: Vec<String> = problems
+            .iter()
+            .map(|p| format!("{}|{}|{}", p.location.rsplit(':').next().unwrap_or(""), p.key, p.message))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "2|modle|unknown key",
+                "5|output.wrap_width|expected a positive integer, found a string",
+                "13|file_bundles[1].dst_ext|missing, expected a string",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_semantic_problems() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("src"))?;
+        fs::write(
+            dir.path().join(BUDDY_TOML),
+            r#"[[file_bundles]]
+bundle_name = "code"
+src_dir = "srcc"
+src_globs = ["**/*.rs"]
+dst_ext = "rs"
+
+[[file_bundles]]
+bundle_name = "code"
+src_dir = "src"
+src_globs = ["[*.md"]
+dst_ext = "md"
+"#,
+        )?;
+
+        let (config, problems) = LayeredConfig::check_from(None, dir.path(), std::iter::empty())?;
+
+        assert!(config.is_some());
+        let messages: Vec<(&str, &str)> = problems.iter().map(|p| (p.key.as_str(), p.message.as_str())).collect();
+        assert!(messages[0].0 == "instructions_file", "{messages:?}");
+        assert_eq!(problems[1].severity, Severity::Warning);
+        assert!(messages[1].0 == "file_bundles[0].src_dir", "{messages:?}");
+        assert_eq!(problems[1].suggestion.as_deref().map(|s| s.ends_with("src'?")), Some(true));
+        assert!(messages[2] == ("file_bundles[1].bundle_name", "duplicate bundle name 'code'"));
+        assert!(messages[3].1.starts_with("invalid glob '[*.md'"), "{messages:?}");
+        Ok(())
+    }
+
+    /// Every field set (no `None`, non empty collections), so all of them are serialized.
+    /// Written as struct literals: a new `Config` field does not build until added here.
+    fn full_config() -> Config {
+        let bundle = FileBundle {
+            bundle_name: "code".to_string(),
+            src_dir: "src".to_string(),
+            dst_ext: "rs".to_string(),
+            src_globs: vec!["**/*.rs".to_string()],
+            exclude_globs: vec!["target/**".to_string()],
+            max_size_kb: Some(1024),
+            max_file_size_kb: Some(256),
+            format: BundleFormatConfig {
+                comment: Some("//".to_string()),
+                toc: true,
+                line_numbers: true,
+                file_meta: true,
+            },
+        };
+        let price = ModelPrice {
+            prompt: 2.5,
+            completion: 10.0,
+        };
+
+        Config {
+            name: "b".to_string(),
+            model: "gpt-4o".to_string(),
+            instructions_file: "instructions.md".to_string(),
+            api_base: Some("https://api.openai.com/v1".to_string()),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            api_key_file: Some("~/.openai_key".to_string()),
+            api_key_cmd: Some("pass openai".to_string()),
+            project_root: Some("..".to_string()),
+            file_bundles: vec![bundle],
+            prices: HashMap::from([("gpt-4o".to_string(), price)]),
+            monthly_budget: Some(20.0),
+            upload_concurrency: Some(4),
+            watch: true,
+            output: OutputConfig::default(),
+            retry: RetryConfig::default(),
+            compact: CompactConfig {
+                threshold_tokens: Some(50_000),
+                ..CompactConfig::default()
+            },
+        }
+    }
+
+    /// The keys of `table` not in `fields`, and the `fields` not in `table`.
+    fn schema_drift(table: &Table, fields: &[Field], path: &str, drift: &mut Vec<String>) {
+        for (key, value) in table.iter() {
+            let key_path = format!("{path}{key}");
+            let Some(field) = fields.iter().find(|f| f.name == key) else {
+                drift.push(format!("{key_path} not in the schema"));
+                continue;
+            };
+            match (field.kind, value) {
+                (Kind::Table(fields), Value::Table(table)) => {
+                    schema_drift(table, fields, &format!("{key_path}."), drift)
+                }
+                (Kind::Tables(fields), Value::Array(items)) => {
+                    for table in items.iter().filter_map(Value::as_table) {
+                        schema_drift(table, fields, &format!("{key_path}[]."), drift);
+                    }
+                }
+                (Kind::Map(fields), Value::Table(map)) => {
+                    for table in map.values().filter_map(Value::as_table) {
+                        schema_drift(table, fields, &format!("{key_path}.*."), drift);
+                    }
+                }
+                _ => (),
+            }
+        }
+        for field in fields.iter().filter(|f| !table.contains_key(f.name)) {
+            drift.push(format!("{path}{} not in the config", field.name));
+        }
+    }
+
+    #[test]
+    fn test_validate_schema_matches_config() -> Result<()> {
+        let Value::Table(table) = Value::try_from(full_config())? else {
+            return Err("Config not serialized as a table".into());
+        };
+
+        let mut drift = Vec::new();
+        schema_drift(&table, CONFIG_FIELDS, "", &mut drift);
+
+        assert!(drift.is_empty(), "{drift:?}");
+        Ok(())
+    }
+}
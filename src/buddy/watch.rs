@@ -0,0 +1,212 @@
+use crate::buddy::config::FileBundle;
+use crate::buddy::instructions::print_render_warnings;
+use crate::buddy::sync::SYNC_LOCK;
+use crate::buddy::{Buddy, DATA_DIR};
+use crate::utils::cli::{ico_err, ico_uploaded};
+use crate::utils::files::{is_base_dir_excluded, read_to_string};
+use crate::Result;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+const DEBOUNCE_MS: u64 = 1000;
+/// Delay before syncing the changes again, when a command was syncing.
+const SYNC_RETRY_MS: u64 = 2000;
+
+/// Keeps the buddy in sync with its sources while alive; dropping it stops the watch.
+pub struct BuddyWatcher {
+    _debouncer: Arc<Mutex<Debouncer<RecommendedWatcher>>>,
+}
+
+/// What the watch thread knows of the instructions.
+struct InstWatch {
+    /// To watch the dirs of new includes (gone once the `BuddyWatcher` is dropped).
+    debouncer: Weak<Mutex<Debouncer<RecommendedWatcher>>>,
+    /// Dirs of the instructions file and its includes, watched already.
+    dirs: HashSet<PathBuf>,
+    /// The instructions file and its includes, as of the last render.
+    files: Vec<PathBuf>,
+}
+
+impl Buddy {
+    /// Watches the `src_dir` of each file bundle, and the instructions file and its includes.
+    /// On change (debounced), the affected bundles are rebuilt and re-uploaded,
+    /// and the instructions re-rendered and re-uploaded when they differ from
+    /// the last upload, in the background.
+    /// Changes made while a command syncs (e.g. `/rf`) are synced after it.
+    pub fn watch(&self) -> Result<BuddyWatcher> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut debouncer = new_debouncer(Duration::from_millis(DEBOUNCE_MS), tx)?;
+
+        // -- Bundle sources
+        for bundle in self.config.file_bundles.iter() {
+            if let Ok(src_dir) = self.dir.join(&bundle.src_dir).canonicalize() {
+                debouncer.watcher().watch(&src_dir, RecursiveMode::Recursive)?;
+            }
+        }
+
+        // -- Instructions and includes (their dirs, as editors often replace the file on save)
+        let debouncer = Arc::new(Mutex::new(debouncer));
+        let mut inst_watch = InstWatch {
+            debouncer: Arc::downgrade(&debouncer),
+            dirs: HashSet::new(),
+            files: Vec::new(),
+        };
+        let inst_files = match self.render_instructions_with_includes() {
+            Ok(rendered) => rendered.files,
+            Err(_) => vec![canonical(&self.instructions_file())],
+        };
+        inst_watch.watch_files(inst_files)?;
+
+        let buddy = self.clone();
+        thread::Builder::new()
+            .name("buddy-watch".to_string())
+            .spawn(move || buddy.run_watch(rx, inst_watch))?;
+
+        Ok(BuddyWatcher { _debouncer: debouncer })
+    }
+}
+
+impl InstWatch {
+    /// Sets the instructions `files`, and watches the dirs not watched yet.
+    fn watch_files(&mut self, files: Vec<PathBuf>) -> Result<()> {
+        let Some(debouncer) = self.debouncer.upgrade() else {
+            return Ok(());
+        };
+        let mut debouncer = debouncer.lock().map_err(|_| "Watch lock poisoned")?;
+        for dir in files.iter().filter_map(|f| f.parent()) {
+            if dir.is_dir() && !self.dirs.contains(dir) {
+                debouncer.watcher().watch(dir, RecursiveMode::NonRecursive)?;
+                self.dirs.insert(dir.to_path_buf());
+            }
+        }
+        self.files = files;
+        Ok(())
+    }
+}
+
+// Private watch functions
+impl Buddy {
+    /// Runs on the watch thread until the `BuddyWatcher` is dropped
+    /// (which closes the channel).
+    fn run_watch(self, rx: Receiver<DebounceEventResult>, mut inst_watch: InstWatch) {
+        let rt = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(err) => {
+                println!("{} Watch stopped: {err}", ico_err());
+                return;
+            }
+        };
+
+        // Changes not synced yet, as a command was syncing.
+        let mut pending: Vec<PathBuf> = Vec::new();
+        loop {
+            let res = if pending.is_empty() {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                rx.recv_timeout(Duration::from_millis(SYNC_RETRY_MS))
+            };
+            match res {
+                Ok(Ok(events)) => pending.extend(events.into_iter().map(|e| e.path)),
+                Ok(Err(err)) => println!("{} Watch error: {err}", ico_err()),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !pending.is_empty() && rt.block_on(self.sync_changes(&pending, &mut inst_watch)) {
+                pending.clear();
+            }
+        }
+    }
+
+    /// Syncs what the changed `paths` affect. Returns `false` if a command is
+    /// syncing, for the changes to be synced later.
+    async fn sync_changes(&self, paths: &[PathBuf], inst_watch: &mut InstWatch) -> bool {
+        // Our own bundle files must not trigger a sync.
+        let data_dir = canonical(&self.dir.join(DATA_DIR));
+        let paths: Vec<&PathBuf> = paths
+            .iter()
+            .filter(|p| !p.starts_with(&data_dir))
+            .filter(|p| !is_base_dir_excluded(p).unwrap_or(false))
+            .collect();
+        if paths.is_empty() {
+            return true;
+        }
+        let Ok(_sync) = SYNC_LOCK.try_lock() else {
+            return false;
+        };
+
+        let bundles: Vec<&FileBundle> = self
+            .config
+            .file_bundles
+            .iter()
+            .filter(|b| {
+                let src_dir = canonical(&self.dir.join(&b.src_dir));
+                paths.iter().any(|p| p.starts_with(&src_dir))
+            })
+            .collect();
+
+        // -- Instructions (the `{{files.*}}` lists change with the bundle sources)
+        if !bundles.is_empty() || paths.iter().any(|p| inst_watch.files.contains(p)) {
+            if let Err(err) = self.sync_instructions(inst_watch).await {
+                println!("{} Watch: cannot upload instructions: {err}", ico_err());
+            }
+        }
+
+        // -- Bundles
+        if bundles.is_empty() {
+            return true;
+        }
+
+        match self.sync_bundles(&bundles, false, false, false).await {
+            Ok(summary) => {
+                for sync in summary.bundles.into_iter().filter(|s| s.num_uploaded > 0) {
+                    println!(
+                        "{} Watch: bundle '{}' synced ({} files, {} KB)",
+                        ico_uploaded(),
+                        sync.bundle_name,
+                        sync.num_files,
+                        sync.size.div_ceil(1024)
+                    );
+                }
+            }
+            Err(err) => println!("{} Watch: cannot sync bundles: {err}", ico_err()),
+        }
+        true
+    }
+
+    /// Re-renders the instructions, and uploads them if they differ from the last upload.
+    async fn sync_instructions(&self, inst_watch: &mut InstWatch) -> Result<()> {
+        let inst_file = self.instructions_file();
+        if !inst_file.exists() {
+            return Ok(());
+        }
+        let rendered = self.render_instructions_with_includes()?;
+        inst_watch.watch_files(rendered.files.clone())?;
+
+        let history = self.instructions_history()?;
+        if let Some(latest) = history.latest() {
+            if history.content(latest.version)? == rendered.text {
+                return Ok(());
+            }
+        }
+        print_render_warnings(&rendered);
+        let inst_content = rendered.text;
+
+        let source = read_to_string(&inst_file)?;
+        let version = self.upload_instructions_content(inst_content, &source, None).await?;
+        println!("{} Watch: instructions uploaded (v{version})", ico_uploaded());
+        Ok(())
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,28 @@
+use crate::buddy::load_attachments;
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_attach, ico_info};
+
+pub fn attach(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let path_or_glob = args.get(0).unwrap_or_default();
+
+        for att in load_attachments(path_or_glob)? {
+            println!(
+                "{} Attached '{}' ({} KB)",
+                ico_attach(),
+                att.path.display(),
+                att.size().div_ceil(1024)
+            );
+            sess.attachments.retain(|a| a.path != att.path);
+            sess.attachments.push(att);
+        }
+        println!(
+            "{} {} file(s) will be sent with your next message",
+            ico_info(),
+            sess.attachments.len()
+        );
+
+        Ok(Flow::Continue)
+    })
+}
@@ -0,0 +1,11 @@
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+
+/// `/compact`: summarizes the conversation into a new, shorter one.
+pub fn compact(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.check_no_active_run().await?;
+        sess.compact().await?;
+        Ok(Flow::Continue)
+    })
+}
@@ -0,0 +1,53 @@
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_check, ico_info};
+
+/// `/fork [name] [n]`, or `/fork <n>` (names are not all digits).
+pub fn fork(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let (name, upto) = match (args.get(0), args.get(1)) {
+            (Some(n), None) if n.parse::<usize>().is_ok() => (None, Some(n)),
+            (name, upto) => (name, upto),
+        };
+        let upto = upto
+            .map(|n| n.parse::<usize>().map_err(|_| format!("Expected a message index, got '{n}'")))
+            .transpose()?;
+
+        sess.check_no_active_run().await?;
+        let fork = sess.buddy.fork_conv(&mut sess.conv, name, upto).await?;
+        let from = sess.conv.name().unwrap_or_default().to_string();
+        sess.conv = fork;
+        println!(
+            "{} Forked '{from}' into '{}', now the current conversation (`/conv {from}` to go back)",
+            ico_check(),
+            sess.conv.name().unwrap_or_default()
+        );
+
+        Ok(Flow::Continue)
+    })
+}
+
+/// `/conv [name]`: lists the named conversations, or switches to one.
+pub fn conv(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let Some(name) = args.get(0) else {
+            let names = sess.buddy.conv_names()?;
+            if names.is_empty() {
+                println!("{} No named conversations (see /fork)", ico_info());
+                return Ok(Flow::Continue);
+            }
+            println!("{} Conversations", ico_info());
+            for name in names {
+                let current = if sess.conv.name() == Some(name.as_str()) { "*" } else { " " };
+                println!("  {current} {name}");
+            }
+            return Ok(Flow::Continue);
+        };
+
+        sess.conv = sess.buddy.switch_conv(name).await?;
+        println!("{} Switched to the conversation '{name}'", ico_check());
+        sess.print_history_summary().await?;
+
+        Ok(Flow::Continue)
+    })
+}
@@ -0,0 +1,25 @@
+use crate::buddy::{export_transcript, ExportFormat};
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::ico_check;
+use std::fs;
+
+/// `/export md|json|html <path> [remote]`
+pub fn export(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let format: ExportFormat = args.get(0).unwrap_or_default().parse()?;
+        let path = args.get(1).unwrap_or_default();
+
+        let entries = match args.get(2) {
+            None => sess.buddy.transcript(&sess.conv).entries()?,
+            Some("remote") => sess.buddy.remote_transcript(&sess.conv).await?,
+            Some(other) => return Err(format!("Expected 'remote', got '{other}'").into()),
+        };
+
+        let title = format!("{} · {}", sess.buddy.name(), *sess.conv);
+        fs::write(path, export_transcript(&title, &entries, format)?)?;
+        println!("{} Exported {} messages to '{path}'", ico_check(), entries.len());
+
+        Ok(Flow::Continue)
+    })
+}
@@ -0,0 +1,48 @@
+use crate::cmd::{commands, find, suggest, usage, CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_err, ico_info};
+
+pub fn help(_sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        // -- Help of one command
+        if let Some(name) = args.get(0) {
+            let name = name.trim_start_matches('/');
+            match find(name) {
+                Some(spec) => {
+                    println!("{}", usage(spec));
+                    println!("    {}", spec.help);
+                    if !spec.aliases.is_empty() {
+                        let aliases: Vec<String> =
+                            spec.aliases.iter().map(|a| format!("/{a}")).collect();
+                        println!("    aliases: {}", aliases.join(", "));
+                    }
+                }
+                None => {
+                    let suggestions = suggest(name);
+                    println!("{} Unknown command '/{name}'", ico_err());
+                    if !suggestions.is_empty() {
+                        println!("    did you mean: /{}", suggestions.join(", /"));
+                    }
+                }
+            }
+            return Ok(Flow::Continue);
+        }
+
+        // -- List all commands
+        let rows: Vec<(String, &str)> =
+            commands().iter().map(|c| (usage(c), c.help)).collect();
+        let width = rows.iter().map(|(u, _)| u.len()).max().unwrap_or(0);
+
+        println!("{} Commands:", ico_info());
+        for (usage, help) in rows {
+            println!("  {usage:<width$}  {help}");
+        }
+        println!("  Anything else is sent to the buddy ('//' to start a message with '/').");
+
+        Ok(Flow::Continue)
+    })
+}
+
+pub fn quit(_sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async { Ok(Flow::Quit) })
+}
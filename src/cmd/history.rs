@@ -0,0 +1,48 @@
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{confirm, ico_info, txt_dim};
+
+const DEFAULT_PAGE_SIZE: usize = 10;
+/// Max page size of the messages API.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// `/history [n]`: the last `n` messages of the conversation, then the older
+/// ones `n` at a time, on demand (each page printed below the newer ones).
+pub fn history(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let page_size = match args.get(0) {
+            Some(n) => n
+                .parse::<usize>()
+                .ok()
+                .filter(|n| (1..=MAX_PAGE_SIZE).contains(n))
+                .ok_or_else(|| format!("Expected a number of messages from 1 to {MAX_PAGE_SIZE}, got '{n}'"))?,
+            None => DEFAULT_PAGE_SIZE,
+        };
+
+        let mut cursor = None;
+        let mut shown = 0;
+        loop {
+            let (msgs, next) = sess.buddy.history_page(&sess.conv, page_size, cursor).await?;
+            if msgs.is_empty() && shown == 0 {
+                println!("{} No messages in this conversation yet", ico_info());
+                break;
+            }
+
+            if shown > 0 {
+                println!("{}", txt_dim("── older messages, before the ones above ──".to_string()));
+            }
+            for msg in msgs.iter().rev() {
+                println!();
+                sess.print_msg(msg);
+            }
+            shown += msgs.len();
+
+            match next {
+                Some(next) if confirm(&format!("Show {page_size} older messages?"), true)? => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        Ok(Flow::Continue)
+    })
+}
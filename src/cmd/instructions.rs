@@ -0,0 +1,110 @@
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{confirm, ico_check, ico_info, txt_dim};
+use crate::utils::files::read_to_string;
+use crate::Result;
+use console::style;
+use similar::{ChangeTag, TextDiff};
+
+/// `/instructions [list|diff|rollback] [n]`
+pub fn instructions(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let version = args.get(1).map(parse_version).transpose()?;
+
+        match args.get(0).unwrap_or("list") {
+            "list" => list(sess)?,
+            "diff" => diff(sess, version)?,
+            "rollback" => {
+                let Some(version) = version else {
+                    return Err("Usage: /instructions rollback <n>".into());
+                };
+                if has_unrecorded_edits(sess)?
+                    && !confirm("The instructions file has edits not in the history, overwrite them?", false)?
+                {
+                    println!("{} Rollback cancelled", ico_info());
+                    return Ok(Flow::Continue);
+                }
+                let new_version = sess.buddy.rollback_instructions(version).await?;
+                println!(
+                    "{} Instructions rolled back to v{version} and uploaded (v{new_version})",
+                    ico_check()
+                );
+            }
+            other => {
+                return Err(format!("Expected 'list', 'diff' or 'rollback', got '{other}'").into())
+            }
+        }
+
+        Ok(Flow::Continue)
+    })
+}
+
+fn list(sess: &Session) -> Result<()> {
+    let history = sess.buddy.instructions_history()?;
+    if history.versions().is_empty() {
+        println!("{} No instructions uploaded yet", ico_info());
+        return Ok(());
+    }
+
+    println!("{} Instructions history", ico_info());
+    for v in history.versions().iter().rev() {
+        let note = v.note.as_deref().map(|n| format!(" ({n})")).unwrap_or_default();
+        println!("  v{:<4} {}{}", v.version, v.uploaded_at, txt_dim(note));
+    }
+
+    Ok(())
+}
+
+/// Diff from `version` (default the latest uploaded) to the rendered instructions file.
+fn diff(sess: &Session, version: Option<u32>) -> Result<()> {
+    let history = sess.buddy.instructions_history()?;
+    let Some(version) = version.or(history.latest().map(|v| v.version)) else {
+        return Err("No instructions uploaded yet".into());
+    };
+    let old = history.content(version)?;
+    let new = sess.buddy.render_instructions()?;
+
+    if old == new {
+        println!("{} Instructions file is the same as v{version}", ico_info());
+        return Ok(());
+    }
+
+    println!("{} Diff v{version} -> instructions file", ico_info());
+    let text_diff = TextDiff::from_lines(&old, &new);
+    for (idx, hunk) in text_diff.unified_diff().context_radius(3).iter_hunks().enumerate() {
+        if idx > 0 {
+            println!("{}", txt_dim("  ...".to_string()));
+        }
+        for change in hunk.iter_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            match change.tag() {
+                ChangeTag::Delete => println!("{}", style(format!("- {line}")).red()),
+                ChangeTag::Insert => println!("{}", style(format!("+ {line}")).green()),
+                ChangeTag::Equal => println!("{}", txt_dim(format!("  {line}"))),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `true` when the instructions file differs from the source of the latest uploaded version,
+/// so a rollback would lose it.
+fn has_unrecorded_edits(sess: &Session) -> Result<bool> {
+    let file = sess.buddy.instructions_file();
+    if !file.exists() {
+        return Ok(false);
+    }
+    let history = sess.buddy.instructions_history()?;
+    let Some(latest) = history.latest() else {
+        return Ok(true);
+    };
+    Ok(read_to_string(&file)? != history.source(latest.version)?)
+}
+
+fn parse_version(arg: &str) -> Result<u32> {
+    arg.trim_start_matches('v')
+        .parse()
+        .map_err(|_| format!("Expected a version number, got '{arg}'").into())
+}
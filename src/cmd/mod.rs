@@ -0,0 +1,362 @@
+mod attach;
+mod compact;
+mod convs;
+mod export;
+mod general;
+mod history;
+mod instructions;
+mod overrides;
+mod refresh;
+mod rewind;
+mod steps;
+mod usage;
+mod watch;
+
+use crate::session::Session;
+use crate::utils::cli::{ico_err, ico_info};
+use crate::Result;
+use std::future::Future;
+use std::pin::Pin;
+
+/// What the chat loop should do once a command has been handled.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+pub type CmdFuture<'a> = Pin<Box<dyn Future<Output = Result<Flow>> + 'a>>;
+
+/// Async command handler. Gets the session (buddy + current conv) and the
+/// already validated arguments.
+pub type CmdHandler = for<'a> fn(&'a mut Session, CmdArgs) -> CmdFuture<'a>;
+
+/// A slash command, as registered in `COMMANDS`.
+pub struct CmdSpec {
+    /// Name without the leading `/`.
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub help: &'static str,
+    pub handler: CmdHandler,
+}
+
+/// Positional argument of a command.
+/// - `required` args must be present.
+/// - `rest` (last only) takes the remainder of the line, spaces included.
+///   Declared with `opt_rest`, it is optional.
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub required: bool,
+    pub rest: bool,
+}
+
+impl ArgSpec {
+    pub const fn req(name: &'static str) -> Self {
+        Self { name, required: true, rest: false }
+    }
+
+    pub const fn opt(name: &'static str) -> Self {
+        Self { name, required: false, rest: false }
+    }
+
+    pub const fn opt_rest(name: &'static str) -> Self {
+        Self { name, required: false, rest: true }
+    }
+}
+
+/// Parsed arguments of a command, in `ArgSpec` order.
+#[derive(Debug, Default)]
+pub struct CmdArgs(Vec<String>);
+
+impl CmdArgs {
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.0.get(idx).map(String::as_str)
+    }
+}
+
+// Registry
+
+static COMMANDS: &[CmdSpec] = &[
+    CmdSpec {
+        name: "help",
+        aliases: &["h", "?"],
+        args: &[ArgSpec::opt("command")],
+        help: "List commands, or show the help of one command",
+        handler: general::help,
+    },
+    CmdSpec {
+        name: "q",
+        aliases: &["quit", "exit"],
+        args: &[],
+        help: "Quit the buddy",
+        handler: general::quit,
+    },
+    CmdSpec {
+        name: "ra",
+        aliases: &["r"],
+        args: &[],
+        help: "Recreate the assistant, re-upload files and start a new conversation",
+        handler: refresh::refresh_all,
+    },
+    CmdSpec {
+        name: "ri",
+        aliases: &[],
+        args: &[],
+        help: "Re-upload the instructions and start a new conversation",
+        handler: refresh::refresh_inst,
+    },
+    CmdSpec {
+        name: "instructions",
+        aliases: &["inst"],
+        args: &[ArgSpec::opt("list|diff|rollback"), ArgSpec::opt("n")],
+        help: "List the uploaded instructions versions, diff the file against one, or roll back to one",
+        handler: instructions::instructions,
+    },
+    CmdSpec {
+        name: "rf",
+        aliases: &[],
+        args: &[],
+        help: "Re-upload the file bundles and start a new conversation",
+        handler: refresh::refresh_files,
+    },
+    CmdSpec {
+        name: "rc",
+        aliases: &[],
+        args: &[],
+        help: "Start a new conversation",
+        handler: refresh::refresh_conv,
+    },
+    CmdSpec {
+        name: "attach",
+        aliases: &["a"],
+        args: &[ArgSpec::req("path|glob")],
+        help: "Attach local file(s) to the next message (also: @path in a message)",
+        handler: attach::attach,
+    },
+    CmdSpec {
+        name: "with",
+        aliases: &[],
+        args: &[ArgSpec::opt_rest("overrides")],
+        help: "Set run overrides (model, instructions, add, tools) for the next message, or with 'session' for the session",
+        handler: overrides::with,
+    },
+    CmdSpec {
+        name: "model",
+        aliases: &[],
+        args: &[ArgSpec::opt("name|default")],
+        help: "Show the model, or use another one for the rest of the session",
+        handler: overrides::model,
+    },
+    CmdSpec {
+        name: "retry",
+        aliases: &[],
+        args: &[],
+        help: "Run the assistant again for a new answer to the last message",
+        handler: rewind::retry,
+    },
+    CmdSpec {
+        name: "edit",
+        aliases: &[],
+        args: &[ArgSpec::opt_rest("message")],
+        help: "Replace the last message (and its answer) with an edited one, and send it",
+        handler: rewind::edit,
+    },
+    CmdSpec {
+        name: "undo",
+        aliases: &[],
+        args: &[],
+        help: "Remove the last message and its answer from the conversation",
+        handler: rewind::undo,
+    },
+    CmdSpec {
+        name: "fork",
+        aliases: &[],
+        args: &[ArgSpec::opt("name"), ArgSpec::opt("n")],
+        help: "Copy the conversation (up to its n-th message) into a new named one, and switch to it",
+        handler: convs::fork,
+    },
+    CmdSpec {
+        name: "conv",
+        aliases: &["convs"],
+        args: &[ArgSpec::opt("name")],
+        help: "List the named conversations, or switch to one",
+        handler: convs::conv,
+    },
+    CmdSpec {
+        name: "compact",
+        aliases: &[],
+        args: &[],
+        help: "Summarize the conversation into a new one, keeping the last turns as they are",
+        handler: compact::compact,
+    },
+    CmdSpec {
+        name: "history",
+        aliases: &["hist"],
+        args: &[ArgSpec::opt("n")],
+        help: "Show the last n messages of the conversation (default 10), then older ones on demand",
+        handler: history::history,
+    },
+    CmdSpec {
+        name: "export",
+        aliases: &[],
+        args: &[ArgSpec::req("md|json|html"), ArgSpec::req("path"), ArgSpec::opt("remote")],
+        help: "Export the conversation transcript (or with 'remote', the full thread from the API)",
+        handler: export::export,
+    },
+    CmdSpec {
+        name: "steps",
+        aliases: &[],
+        args: &[ArgSpec::opt("on|off")],
+        help: "Show the steps (tool calls, ...) of the last run, or print them live during the runs",
+        handler: steps::steps,
+    },
+    CmdSpec {
+        name: "usage",
+        aliases: &["cost"],
+        args: &[],
+        help: "Show token usage and cost for this session, today and this month",
+        handler: usage::usage,
+    },
+    CmdSpec {
+        name: "watch",
+        aliases: &["w"],
+        args: &[ArgSpec::opt("on|off")],
+        help: "Re-sync bundles and instructions in the background when their files change",
+        handler: watch::watch,
+    },
+];
+
+pub fn commands() -> &'static [CmdSpec] {
+    COMMANDS
+}
+
+/// Finds a command by name or alias (without the leading `/`).
+pub fn find(name: &str) -> Option<&'static CmdSpec> {
+    COMMANDS
+        .iter()
+        .find(|c| c.name == name || c.aliases.contains(&name))
+}
+
+/// Returns the closest command names for an unknown command.
+pub fn suggest(name: &str) -> Vec<&'static str> {
+    const MAX_DISTANCE: usize = 2;
+    // Every name starts with "", and is close enough to a short one.
+    if name.is_empty() {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(usize, &'static str)> = COMMANDS
+        .iter()
+        .filter_map(|c| {
+            let best = std::iter::once(&c.name)
+                .chain(c.aliases.iter())
+                .map(|n| strsim::levenshtein(name, n))
+                .min()?;
+            let is_prefix = c.name.starts_with(name) || name.starts_with(c.name);
+            (best <= MAX_DISTANCE || is_prefix).then_some((best, c.name))
+        })
+        .collect();
+
+    scored.sort();
+    scored.into_iter().map(|(_, n)| n).take(3).collect()
+}
+
+// Dispatch
+
+/// Routes one line of user input: slash commands go to their handler,
+/// everything else is sent to the assistant as chat text.
+/// A leading `//` escapes the slash (e.g. `//etc/hosts ...` is sent as chat).
+pub async fn dispatch(sess: &mut Session, input: &str) -> Result<Flow> {
+    let line = match input.trim().strip_prefix('/') {
+        Some(line) if !line.starts_with('/') => line,
+        Some(escaped) => {
+            sess.chat(escaped).await?;
+            return Ok(Flow::Continue);
+        }
+        None => {
+            sess.chat(input).await?;
+            return Ok(Flow::Continue);
+        }
+    };
+
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    if name.is_empty() {
+        println!("{} Type /help for the list of commands.", ico_info());
+        return Ok(Flow::Continue);
+    }
+
+    let Some(spec) = find(name) else {
+        let suggestions = suggest(name);
+        if suggestions.is_empty() {
+            println!("{} Unknown command '/{name}'. Type /help for the list.", ico_err());
+        } else {
+            let suggestions: Vec<String> =
+                suggestions.iter().map(|s| format!("/{s}")).collect();
+            println!(
+                "{} Unknown command '/{name}'. Did you mean {}?",
+                ico_err(),
+                suggestions.join(", ")
+            );
+        }
+        return Ok(Flow::Continue);
+    };
+
+    let args = match parse_args(spec, rest) {
+        Ok(args) => args,
+        Err(err) => {
+            println!("{} {err}", ico_err());
+            println!("{} usage: {}", ico_info(), usage(spec));
+            return Ok(Flow::Continue);
+        }
+    };
+
+    (spec.handler)(sess, args).await
+}
+
+fn parse_args(spec: &CmdSpec, rest: &str) -> Result<CmdArgs> {
+    let mut values = Vec::new();
+    let mut remaining = rest.trim();
+
+    for arg in spec.args {
+        if remaining.is_empty() {
+            if arg.required {
+                return Err(format!("Missing argument <{}>", arg.name).into());
+            }
+            break;
+        }
+
+        if arg.rest {
+            values.push(remaining.to_string());
+            remaining = "";
+        } else {
+            let (value, tail) = remaining
+                .split_once(char::is_whitespace)
+                .unwrap_or((remaining, ""));
+            values.push(value.to_string());
+            remaining = tail.trim_start();
+        }
+    }
+
+    if !remaining.is_empty() {
+        return Err(format!("Unexpected argument(s) '{remaining}'").into());
+    }
+
+    Ok(CmdArgs(values))
+}
+
+/// e.g. `/attach <path>` or `/history [n]`
+pub fn usage(spec: &CmdSpec) -> String {
+    let mut usage = format!("/{}", spec.name);
+    for arg in spec.args {
+        let arg = match (arg.required, arg.rest) {
+            (true, true) => format!(" <{}...>", arg.name),
+            (false, true) => format!(" [{}...]", arg.name),
+            (true, false) => format!(" <{}>", arg.name),
+            (false, false) => format!(" [{}]", arg.name),
+        };
+        usage.push_str(&arg);
+    }
+    usage
+}
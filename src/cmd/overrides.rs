@@ -0,0 +1,75 @@
+use crate::ais::assistant::RunOverrides;
+use crate::buddy::{describe_overrides, parse_overrides};
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_check, ico_info};
+
+/// `/with [session|clear] [key=value...]`: run overrides for the next message,
+/// or for the rest of the session. Without overrides, shows the current ones.
+pub fn with(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let text = args.get(0).unwrap_or_default().trim();
+        let (session, text) = match text.split_once(char::is_whitespace).unwrap_or((text, "")) {
+            ("clear", _) => {
+                sess.overrides = RunOverrides::default();
+                sess.next_overrides = RunOverrides::default();
+                println!("{} Run overrides cleared", ico_check());
+                return Ok(Flow::Continue);
+            }
+            ("session", rest) => (true, rest.trim()),
+            _ => (false, text),
+        };
+
+        if text.is_empty() {
+            print_overrides(sess);
+            return Ok(Flow::Continue);
+        }
+
+        let overrides = parse_overrides(text)?;
+        if session {
+            sess.overrides = sess.overrides.merged(&overrides);
+            println!("{} For this session: {}", ico_check(), describe_overrides(&sess.overrides));
+        } else {
+            sess.next_overrides = sess.next_overrides.merged(&overrides);
+            println!("{} For the next message: {}", ico_check(), describe_overrides(&sess.next_overrides));
+        }
+
+        Ok(Flow::Continue)
+    })
+}
+
+/// `/model [name|default]`: the model of the runs for the rest of the session.
+pub fn model(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        match args.get(0) {
+            None => {
+                let model = sess.overrides.model.as_deref().unwrap_or(sess.buddy.model());
+                println!("{} Model: {model} (assistant: {})", ico_info(), sess.buddy.model());
+            }
+            Some("default") => {
+                sess.overrides.model = None;
+                println!("{} Back to the assistant model ({})", ico_check(), sess.buddy.model());
+            }
+            Some(model) => {
+                sess.overrides.model = Some(model.to_string());
+                println!("{} Model for this session: {model} (`/model default` to go back)", ico_check());
+            }
+        }
+
+        Ok(Flow::Continue)
+    })
+}
+
+fn print_overrides(sess: &Session) {
+    if sess.overrides.is_empty() && sess.next_overrides.is_empty() {
+        println!("{} No run overrides, the assistant settings are used", ico_info());
+        return;
+    }
+    println!("{} Run overrides", ico_info());
+    if !sess.overrides.is_empty() {
+        println!("  session      {}", describe_overrides(&sess.overrides));
+    }
+    if !sess.next_overrides.is_empty() {
+        println!("  next message {}", describe_overrides(&sess.next_overrides));
+    }
+}
@@ -0,0 +1,42 @@
+use crate::buddy::Buddy;
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+
+pub fn refresh_all(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let dir = sess.buddy.dir().to_path_buf();
+        let verbose_steps = sess.buddy.output().verbose_steps;
+        sess.buddy = Buddy::init_from_dir(dir, true).await?;
+        sess.buddy.set_verbose_steps(verbose_steps);
+        sess.buddy.upload_files(true).await?;
+        sess.conv = sess.buddy.load_or_create_conv(true).await?;
+        // The watcher syncs to the previous assistant.
+        if sess.watcher.is_some() {
+            sess.start_watch()?;
+        }
+        Ok(Flow::Continue)
+    })
+}
+
+pub fn refresh_inst(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.buddy.upload_instructions().await?;
+        sess.conv = sess.buddy.load_or_create_conv(true).await?;
+        Ok(Flow::Continue)
+    })
+}
+
+pub fn refresh_files(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.buddy.upload_files(true).await?;
+        sess.conv = sess.buddy.load_or_create_conv(true).await?;
+        Ok(Flow::Continue)
+    })
+}
+
+pub fn refresh_conv(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.conv = sess.buddy.load_or_create_conv(true).await?;
+        Ok(Flow::Continue)
+    })
+}
@@ -0,0 +1,52 @@
+use crate::buddy::load_attachments;
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_check, ico_warn, prompt_edit, txt_dim};
+
+/// `/retry`
+pub fn retry(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.retry().await?;
+        Ok(Flow::Continue)
+    })
+}
+
+/// `/undo`
+pub fn undo(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.check_no_active_run().await?;
+        let (conv, undone) = sess.buddy.undo(&sess.conv).await?;
+        sess.conv = conv;
+        let first_line = undone.text.lines().next().unwrap_or_default().to_string();
+        println!("{} Removed the last message and its answer: {}", ico_check(), txt_dim(first_line));
+        Ok(Flow::Continue)
+    })
+}
+
+/// `/edit [new message]`: replaces the last message (and its answer) with the new
+/// one, edited from the last one when not given, and sends it with the same attachments.
+pub fn edit(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        sess.check_no_active_run().await?;
+        let last = sess.buddy.last_user_msg(&sess.conv).await?;
+        let msg = match args.get(0) {
+            Some(msg) => msg.to_string(),
+            None => prompt_edit("Edit", &last.text)?,
+        };
+        if msg.trim().is_empty() {
+            return Err("Empty message, the conversation is unchanged".into());
+        }
+
+        let (conv, undone) = sess.buddy.undo(&sess.conv).await?;
+        sess.conv = conv;
+        for path in undone.attachments.iter() {
+            match load_attachments(path) {
+                Ok(attachments) => sess.attachments.extend(attachments),
+                Err(err) => println!("{} Not attached again: {err}", ico_warn()),
+            }
+        }
+
+        sess.chat(&msg).await?;
+        Ok(Flow::Continue)
+    })
+}
@@ -0,0 +1,32 @@
+use crate::ais::steps::print_step;
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_check, ico_info};
+
+/// `/steps [on|off]`: the steps of the last run, or turns their live printing on/off.
+pub fn steps(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let on = match args.get(0) {
+            Some("on") => true,
+            Some("off") => false,
+            Some(other) => return Err(format!("Expected 'on' or 'off', got '{other}'").into()),
+            None => {
+                let Some((run_id, steps)) = sess.buddy.last_run_steps(&sess.conv).await? else {
+                    println!("{} No run in this conversation yet", ico_info());
+                    return Ok(Flow::Continue);
+                };
+                println!("{} Steps of the last run ({run_id})", ico_info());
+                for step in steps.iter() {
+                    print_step(step);
+                }
+                return Ok(Flow::Continue);
+            }
+        };
+
+        sess.buddy.set_verbose_steps(on);
+        let state = if on { "on" } else { "off" };
+        println!("{} Live run steps {state}", ico_check());
+
+        Ok(Flow::Continue)
+    })
+}
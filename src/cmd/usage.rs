@@ -0,0 +1,44 @@
+use crate::buddy::{this_month, today, TokenUsage};
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::{ico_info, txt_dim};
+
+pub fn usage(sess: &mut Session, _args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let log = sess.buddy.usage_log()?;
+        let month = this_month();
+
+        println!("{} Usage of buddy '{}'", ico_info(), sess.buddy.name());
+        print_line("session", &sess.usage);
+        print_line("today", &log.total(&today()));
+        let month_total = log.total(&month);
+        print_line("month", &month_total);
+
+        if let Some(budget) = sess.buddy.monthly_budget() {
+            let pct = if budget > 0. { month_total.cost / budget * 100. } else { 0. };
+            println!("  {:<10} ${:.2} of ${:.2} ({:.0}%)", "budget", month_total.cost, budget, pct);
+        }
+
+        let by_model = log.by_model(&month);
+        if !by_model.is_empty() {
+            println!("  {}", txt_dim(format!("{month} by model:")));
+            for (model, usage) in by_model.iter() {
+                print_line(model, usage);
+            }
+        }
+
+        Ok(Flow::Continue)
+    })
+}
+
+fn print_line(label: &str, usage: &TokenUsage) {
+    println!(
+        "  {:<10} {:>4} runs  {:>9} prompt  {:>9} completion  {:>9} total  ${:.4}",
+        label,
+        usage.runs,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.total_tokens(),
+        usage.cost
+    );
+}
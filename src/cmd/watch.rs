@@ -0,0 +1,22 @@
+use crate::cmd::{CmdArgs, CmdFuture, Flow};
+use crate::session::Session;
+use crate::utils::cli::ico_check;
+
+pub fn watch(sess: &mut Session, args: CmdArgs) -> CmdFuture<'_> {
+    Box::pin(async move {
+        let on = match args.get(0) {
+            Some("on") => true,
+            Some("off") => false,
+            Some(other) => return Err(format!("Expected 'on' or 'off', got '{other}'").into()),
+            None => sess.watcher.is_none(),
+        };
+
+        if on {
+            sess.start_watch()?;
+        } else if sess.watcher.take().is_some() {
+            println!("{} Watch stopped", ico_check());
+        }
+
+        Ok(Flow::Continue)
+    })
+}
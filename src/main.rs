@@ -1,80 +1,90 @@
 mod ais;
+mod args;
 mod buddy;
+mod cmd;
 mod error;
+mod session;
 mod utils;
 
-use textwrap::wrap;
-use crate::buddy::Buddy;
-use crate::utils::cli::{ico_err, ico_res, prompt, txt_res};
+use crate::args::{Args, CliCmd, ConfigCmd};
+use crate::buddy::{describe_overrides, export_conv, init_buddy, Buddy, InitOptions, LayeredConfig};
+use crate::cmd::Flow;
+use crate::session::Session;
+use crate::utils::cli::{ico_check, ico_err, is_interrupted, prompt};
+use crate::utils::interrupt::listen_ctrl_c;
 pub use self::error::{Error, Result};
+use clap::Parser;
 
 #[tokio::main]
 async fn main() {
     println!();
     match  start().await {
         Ok(_) => print!("\nSuccess!\n"),
-        Err(e) => eprintln!("\nError: {}\n", e),
+        Err(e) => {
+            eprintln!("\nError: {}\n", e);
+            std::process::exit(1);
+        }
     }
 }
 
-const DEFAULT_DIR: &str = "buddy";
-
-// Types
-
-// Input command from the user
-#[derive(Debug)]
-enum Cmd {
-    Quit,
-    Chat(String),
-    RefreshAll,
-    RefreshConv,
-    RefreshInst,
-    RefreshFiles,
-}
-
-impl Cmd {
-    fn from_input(input: impl Into<String>) -> Self {
-        let input = input.into();
+async fn start() -> Result<()> {
+    let args = Args::parse();
 
-        if input == "/q" {
-            Self::Quit
-        } else if input == "/r" || input == "/ra" {
-            Self::RefreshAll
-        } else if input == "/ri" {
-            Self::RefreshInst
-        } else if input == "/rf" {
-            Self::RefreshFiles
-        } else if input == "/rc" {
-            Self::RefreshConv
-        } else {
-            Self::Chat(input)
+    match args.cmd.unwrap_or(CliCmd::Chat) {
+        CliCmd::Chat => chat(&args.dir).await,
+        CliCmd::Init { dir, name, model, yes, force } => {
+            let dir = dir.unwrap_or(args.dir);
+            let interactive = !yes && console::user_attended();
+            init_buddy(dir.as_ref(), InitOptions { name, model, force, interactive })
         }
+        CliCmd::Sync { dry_run: true, force } => {
+            Buddy::dry_run_from_dir(&args.dir, force).await?.print();
+            Ok(())
+        }
+        CliCmd::Sync { dry_run: false, force } => {
+            let buddy = Buddy::init_from_dir(&args.dir, false).await?;
+            buddy.upload_files(force).await?;
+            Ok(())
+        }
+        CliCmd::Validate { strict } => LayeredConfig::validate(args.dir.as_ref(), strict),
+        CliCmd::Export { format, out, remote } => {
+            let count = export_conv(args.dir.as_ref(), format.parse()?, out.as_ref(), remote).await?;
+            println!("{} Exported {count} messages to '{out}'", ico_check());
+            Ok(())
+        }
+        CliCmd::Config { cmd: ConfigCmd::Show } => LayeredConfig::load(args.dir.as_ref())?.print(),
     }
 }
 
-#[allow(unused)]
-async fn start() -> Result<()> {
-    let mut buddy = Buddy::init_from_dir(DEFAULT_DIR, false).await?;
-
-    let mut conv = buddy.load_or_create_conv(false).await?;
+async fn chat(dir: &str) -> Result<()> {
+    listen_ctrl_c();
+    let mut sess = Session::start(dir).await?;
 
     loop {
         println!();
-        let input = prompt("Ask away")?;
-        let cmd = Cmd::from_input(input);
+        let overrides = sess.overrides.merged(&sess.next_overrides);
+        let text = if overrides.is_empty() {
+            "Ask away".to_string()
+        } else {
+            format!("Ask away ({})", describe_overrides(&overrides))
+        };
+        let input = match prompt(&text) {
+            Ok(input) => input,
+            // Ctrl-C at the prompt quits (during a run, it cancels the run, and
+            // it is ignored during the other commands, see `listen_ctrl_c`).
+            Err(err) if is_interrupted(&err) => break,
+            Err(err) => return Err(err),
+        };
 
-        match cmd {
-            Cmd::Quit => break,
-            Cmd::Chat(msg) => {
-                let res = buddy.chat(&conv, &msg).await?; // Borrow `msg` here
-                let res = wrap(&res, 80).join("\n");
-                println!(" -> {} {}", ico_res(), txt_res(res));
-            }
-            other => println!("{} command not supported {:?}", ico_err(), other),
+        // Command and chat errors are reported, but do not end the session.
+        match cmd::dispatch(&mut sess, &input).await {
+            Ok(Flow::Quit) => break,
+            Ok(Flow::Continue) => (),
+            Err(err) => println!("{} {}", ico_err(), err),
         }
     }
 
-    println!(" -> buddy {} - conv {:?}", buddy.name(), conv);
+    println!(" -> buddy {} - conv {:?}", sess.buddy.name(), sess.conv);
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,279 @@
+use crate::ais::assistant::{RunOverrides, RunRes, ThreadMsg};
+use async_openai::types::{MessageRole, RunStatus};
+use crate::buddy::{load_mentions, Attachment, Buddy, BuddyWatcher, Conv, TokenUsage, TranscriptEntry};
+use crate::utils::cli::{ico_check, ico_info, ico_res, ico_user, ico_warn, select, txt_dim, txt_res};
+use chrono::{DateTime, Local};
+use crate::Result;
+use std::path::Path;
+use textwrap::wrap;
+
+const WAIT_RUN: &str = "Wait for its answer";
+const CANCEL_RUN: &str = "Cancel it";
+const LEAVE_RUN: &str = "Leave it (e.g. another session is running it)";
+const ACTIVE_RUN_ERR: &str = "A run is still active on this conversation, the message was not sent";
+const ACTIVE_RUN_COPY_ERR: &str = "A run is still active on this conversation, it was left unchanged";
+
+/// State of the interactive chat loop: the buddy and its current conversation.
+/// This is what the slash command handlers operate on.
+pub struct Session {
+    pub buddy: Buddy,
+    pub conv: Conv,
+    /// Usage of all the runs of this session.
+    pub usage: TokenUsage,
+    /// Files staged with `/attach`, sent with the next message.
+    pub attachments: Vec<Attachment>,
+    /// Set while the watch mode is on (see `/watch`).
+    pub watcher: Option<BuddyWatcher>,
+    /// Run overrides for the rest of the session (see `/with session` and `/model`).
+    pub overrides: RunOverrides,
+    /// Run overrides for the next message only, on top of `overrides` (see `/with`).
+    pub next_overrides: RunOverrides,
+}
+
+impl Session {
+    pub async fn start(dir: impl AsRef<Path>) -> Result<Self> {
+        let buddy = Buddy::init_from_dir(dir, false).await?;
+        console::set_colors_enabled(buddy.output().color);
+        let conv = buddy.load_or_create_conv(false).await?;
+
+        let mut sess = Session {
+            buddy,
+            conv,
+            usage: TokenUsage::default(),
+            attachments: Vec::new(),
+            watcher: None,
+            overrides: RunOverrides::default(),
+            next_overrides: RunOverrides::default(),
+        };
+        sess.print_history_summary().await?;
+        // Checked again before each message, it must not prevent the start.
+        if let Err(err) = sess.recover_active_run().await {
+            println!("{} Cannot check for an active run: {err}", ico_warn());
+        }
+        if sess.buddy.watch_on_start() {
+            sess.start_watch()?;
+        }
+
+        Ok(sess)
+    }
+
+    /// Summarizes the last `output.history_on_load` turns of the conversation,
+    /// one line per message.
+    pub async fn print_history_summary(&self) -> Result<()> {
+        let turns = self.buddy.output().history_on_load;
+        if turns == 0 {
+            return Ok(());
+        }
+        let (msgs, _) = self.buddy.history_page(&self.conv, turns * 2, None).await?;
+        if msgs.is_empty() {
+            return Ok(());
+        }
+
+        println!("{} Last messages", ico_info());
+        let width = match self.buddy.output().wrap_width {
+            0 => 80,
+            width => width,
+        };
+        for msg in msgs.iter().rev() {
+            let ico = if msg.role == MessageRole::User { ico_user() } else { ico_res() };
+            let line = msg.text.lines().find(|l| !l.trim().is_empty()).unwrap_or_default().trim();
+            let line = if line.chars().count() > width {
+                format!("{}…", line.chars().take(width - 1).collect::<String>())
+            } else {
+                line.to_string()
+            };
+            println!("  {ico} {}", txt_dim(line));
+        }
+
+        Ok(())
+    }
+
+    /// Offers to wait for or cancel the run left active on the conversation
+    /// (e.g. by a session that crashed), as it blocks new messages.
+    /// Returns whether the conversation is free for a new run.
+    pub async fn recover_active_run(&mut self) -> Result<bool> {
+        let Some(run) = self.buddy.active_run(&self.conv).await? else {
+            return Ok(true);
+        };
+        let started = DateTime::from_timestamp(run.created_at, 0)
+            .map(|at| at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!(
+            "{} A run is still active on this conversation ({:?}, started {started})",
+            ico_warn(),
+            run.status
+        );
+
+        // -- Being cancelled already, or waiting for tool outputs that will never come
+        if run.status == RunStatus::Cancelling {
+            self.buddy.cancel_run(&self.conv, &run.id).await?;
+            println!("{} Run cancelled", ico_check());
+            return Ok(true);
+        }
+        let mut choices = vec![WAIT_RUN, CANCEL_RUN, LEAVE_RUN];
+        if run.status == RunStatus::RequiresAction {
+            choices.retain(|c| *c != WAIT_RUN);
+        }
+
+        match choices[select("What to do with it?", &choices, 0)?] {
+            WAIT_RUN => {
+                let run_res = self.buddy.await_run(&self.conv, &run.id).await?;
+                self.handle_answer(&run_res).await?;
+                Ok(true)
+            }
+            CANCEL_RUN => {
+                self.buddy.cancel_run(&self.conv, &run.id).await?;
+                println!("{} Run cancelled", ico_check());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Fails if a run is still active on the conversation (see `recover_active_run`),
+    /// before it is copied to a new thread (`/undo`, `/edit`, `/fork`, `/compact`),
+    /// which would miss its answer.
+    pub async fn check_no_active_run(&mut self) -> Result<()> {
+        if !self.recover_active_run().await? {
+            return Err(ACTIVE_RUN_COPY_ERR.into());
+        }
+        Ok(())
+    }
+
+    /// Prints a message of the conversation the way it was when live.
+    pub fn print_msg(&self, msg: &ThreadMsg) {
+        let at = DateTime::from_timestamp(msg.created_at, 0)
+            .map(|at| at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!("{}", txt_dim(at));
+        if msg.role == MessageRole::User {
+            println!("{} {}", ico_user(), msg.text);
+        } else {
+            self.print_answer(&msg.text);
+        }
+    }
+
+    fn print_answer(&self, text: &str) {
+        let res = match self.buddy.output().wrap_width {
+            0 => text.to_string(),
+            width => wrap(text, width).join("\n"),
+        };
+        println!(" -> {} {}", ico_res(), txt_res(res));
+    }
+
+    /// Starts (or restarts, e.g. after the buddy was re-initialized) the watch mode.
+    pub fn start_watch(&mut self) -> Result<()> {
+        self.watcher = None;
+        self.watcher = Some(self.buddy.watch()?);
+        println!("{} Watching the bundle sources and instructions", ico_check());
+        Ok(())
+    }
+
+    /// Sends `msg` to the buddy on the current conversation and prints the answer.
+    /// The staged attachments and the `@path` mentions of `msg` are sent along.
+    /// The answer is logged to the conversation transcript (the message is, by `Buddy::chat`).
+    pub async fn chat(&mut self, msg: &str) -> Result<()> {
+        if !self.recover_active_run().await? {
+            return Err(ACTIVE_RUN_ERR.into());
+        }
+
+        let staged = std::mem::take(&mut self.attachments);
+        let mut attachments = staged.clone();
+        for att in load_mentions(msg) {
+            if !attachments.iter().any(|a| a.path == att.path) {
+                attachments.push(att);
+            }
+        }
+
+        let overrides = self.take_run_overrides();
+        let run_res = match self.buddy.chat(&self.conv, msg, &attachments, &overrides).await {
+            Ok(run_res) => run_res,
+            Err(err) => {
+                // Staged again, for the message to be sent again.
+                self.attachments = staged;
+                return Err(err);
+            }
+        };
+        self.handle_answer(&run_res).await
+    }
+
+    /// Runs the assistant again on the current conversation (see `Buddy::retry`),
+    /// and prints the new answer.
+    pub async fn retry(&mut self) -> Result<()> {
+        if !self.recover_active_run().await? {
+            return Err(ACTIVE_RUN_ERR.into());
+        }
+        let overrides = self.take_run_overrides();
+        let run_res = self.buddy.retry(&self.conv, &overrides).await?;
+        self.handle_answer(&run_res).await
+    }
+
+    /// The overrides of the next run, clearing the ones for the next message only.
+    fn take_run_overrides(&mut self) -> RunOverrides {
+        let next = std::mem::take(&mut self.next_overrides);
+        self.overrides.merged(&next)
+    }
+
+    /// Replaces the current conversation with a compacted one (see `Buddy::compact_conv`).
+    pub async fn compact(&mut self) -> Result<()> {
+        let compaction = self.buddy.compact_conv(&self.conv).await?;
+        self.conv = compaction.conv;
+        println!(
+            "{} Conversation compacted: {} → {}",
+            ico_check(),
+            compaction.before,
+            compaction.after
+        );
+        self.record_usage(&compaction.summary_run)
+    }
+
+    /// Logs the answer to the transcript, prints it, and records its usage.
+    /// Compacts the conversation when the run went over `compact.threshold_tokens`
+    /// (a failed compaction is only a warning, the answer is there).
+    async fn handle_answer(&mut self, run_res: &RunRes) -> Result<()> {
+        let transcript = self.buddy.transcript(&self.conv);
+        transcript.append(&TranscriptEntry::assistant(&run_res.text, &run_res.run_id, &run_res.model))?;
+        self.print_answer(&run_res.text);
+        self.record_usage(run_res)?;
+
+        let prompt_tokens = run_res.usage.as_ref().map(|u| u.prompt_tokens as u64);
+        if let (Some(threshold), Some(prompt_tokens)) = (self.buddy.compact_threshold(), prompt_tokens) {
+            if prompt_tokens > threshold {
+                println!(
+                    "{} The last run used {prompt_tokens} prompt tokens (threshold {threshold}), compacting the conversation",
+                    ico_info()
+                );
+                if let Err(err) = self.compact().await {
+                    println!("{} Conversation not compacted: {err}", ico_warn());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the usage of a run, and prints it (if `output.show_usage`).
+    fn record_usage(&mut self, run_res: &RunRes) -> Result<()> {
+        let Some(usage) = self.buddy.record_usage(run_res)? else {
+            return Ok(());
+        };
+        self.usage += usage;
+        if !self.buddy.output().show_usage {
+            return Ok(());
+        }
+        let cost = if self.buddy.has_price_for(&run_res.model) {
+            format!("${:.4} (session ${:.4})", usage.cost, self.usage.cost)
+        } else {
+            format!("no price for '{}' in buddy.toml", run_res.model)
+        };
+        println!(
+            "    {}",
+            txt_dim(format!(
+                "{} prompt + {} completion tokens · {}",
+                usage.prompt_tokens, usage.completion_tokens, cost
+            ))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,455 @@
+use crate::utils::files::XFile;
+use crate::Result;
+use chrono::{DateTime, Local};
+use derive_more::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bytes inspected to detect binary files.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Size limits of a bundle, in bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct BundleLimits {
+    /// Max size of one bundle file. Above it, the bundle is split into parts.
+    pub max_bundle_size: u64,
+    /// Files bigger than this are skipped.
+    pub max_file_size: u64,
+}
+
+/// How the files are rendered in a bundle.
+#[derive(Debug, Clone)]
+pub struct BundleFormat {
+    /// Comment style of the file headers (and table of contents).
+    pub comment: CommentStyle,
+    /// Paths in the headers are relative to it (when the file is under it).
+    pub base_dir: Option<PathBuf>,
+    /// Lists the files of each part at its top.
+    pub toc: bool,
+    /// Prefixes each line with its line number in the source file.
+    pub line_numbers: bool,
+    /// Adds the size and last modified time to the file headers.
+    pub file_meta: bool,
+}
+
+impl Default for BundleFormat {
+    fn default() -> Self {
+        Self {
+            comment: CommentStyle::Line("//".to_string()),
+            base_dir: None,
+            toc: false,
+            line_numbers: false,
+            file_meta: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommentStyle {
+    /// e.g. `//`, `#`
+    Line(String),
+    /// e.g. `<!--` `-->`
+    Block(String, String),
+}
+
+impl CommentStyle {
+    /// Comment style matching a bundle file extension (`#` when unknown).
+    pub fn for_ext(ext: &str) -> Self {
+        let line = |p: &str| CommentStyle::Line(p.to_string());
+        let block = |o: &str, c: &str| CommentStyle::Block(o.to_string(), c.to_string());
+
+        match ext {
+            "rs" | "js" | "mjs" | "ts" | "tsx" | "jsx" | "go" | "java" | "kt" | "c" | "h"
+            | "cpp" | "hpp" | "cs" | "swift" | "scala" | "dart" | "php" | "zig" => line("//"),
+            "sql" | "lua" | "hs" => line("--"),
+            "md" | "html" | "htm" | "xml" | "svg" | "vue" => block("<!--", "-->"),
+            "css" | "scss" => block("/*", "*/"),
+            _ => line("#"),
+        }
+    }
+
+    fn comment(&self, text: &str) -> String {
+        match self {
+            CommentStyle::Line(prefix) => format!("{prefix} {text}"),
+            CommentStyle::Block(open, close) => format!("{open} {text} {close}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Display)]
+pub enum SkipReason {
+    #[display("binary")]
+    Binary,
+    #[display("{} KB > max {} KB", size / 1024, max / 1024)]
+    TooLarge { size: u64, max: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// What `bundle_to_files` wrote, and what it left out.
+#[derive(Debug, Default)]
+pub struct BundleReport {
+    /// `dst_file` itself, or `<stem>-part1.<ext>`, `<stem>-part2.<ext>`, ...
+    /// when the bundle had to be split.
+    pub parts: Vec<PathBuf>,
+    pub num_files: usize,
+    pub size: u64,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Bundles `files` into `dst_file`, splitting it into numbered parts at file
+/// boundaries when it would exceed `limits.max_bundle_size`.
+/// Binary files and files over `limits.max_file_size` are skipped (see `BundleReport::skipped`).
+/// Previous parts of the same bundle are removed first.
+pub fn bundle_to_files(
+    files: Vec<PathBuf>,
+    dst_file: &Path,
+    limits: BundleLimits,
+    format: &BundleFormat,
+) -> Result<BundleReport> {
+    let mut report = BundleReport::default();
+
+    // The table of contents title of a part, with room for its part numbers.
+    let toc_title_len = if format.toc { toc_title(format, Some((9999, 9999))).len() + 1 } else { 0 };
+
+    // -- Render the files and dispatch them in parts.
+    let mut parts: Vec<Part> = vec![Part::new(toc_title_len)];
+    for file in files {
+        if !file.is_file() {
+            return Err(format!("Cannot bundle '{:?}' is not a file.", file).into());
+        }
+
+        let meta = file.metadata()?;
+        let size = meta.len();
+        let max_size = limits.max_file_size.min(limits.max_bundle_size);
+        if size > max_size {
+            let reason = SkipReason::TooLarge { size, max: max_size };
+            report.skipped.push(SkippedFile { path: file, reason });
+            continue;
+        }
+
+        let Some(content) = read_text(&file)? else {
+            let reason = SkipReason::Binary;
+            report.skipped.push(SkippedFile { path: file, reason });
+            continue;
+        };
+
+        let path = display_path(&file, format.base_dir.as_deref());
+        let mut header = format!("=== file path: {path}");
+        if format.file_meta {
+            let modified: DateTime<Local> = meta.modified()?.into();
+            header.push_str(&format!(
+                " | {} | modified {}",
+                human_size(size),
+                modified.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        let toc_line = format
+            .toc
+            .then(|| format.comment.comment(&format!("- {path} ({})", human_size(size))));
+        let section = format!(
+            "\n{}\n\n{}\n\n\n\n",
+            format.comment.comment(&header),
+            render_content(&content, format.line_numbers)
+        );
+
+        // Even alone in its part, the rendered file (header, line numbers, ...) must fit.
+        let entry_len = section.len() + toc_line.as_ref().map(|l| l.len() + 1).unwrap_or(0);
+        if (toc_title_len + entry_len) as u64 > limits.max_bundle_size {
+            let reason = SkipReason::TooLarge {
+                size: (toc_title_len + entry_len) as u64,
+                max: limits.max_bundle_size,
+            };
+            report.skipped.push(SkippedFile { path: file, reason });
+            continue;
+        }
+
+        let current = parts.last_mut().expect("parts is never empty");
+        if !current.sections.is_empty()
+            && (current.len + entry_len) as u64 > limits.max_bundle_size
+        {
+            parts.push(Part::new(toc_title_len));
+        }
+        let current = parts.last_mut().expect("parts is never empty");
+        current.len += entry_len;
+        current.sections.push(section);
+        current.toc.extend(toc_line);
+        report.num_files += 1;
+    }
+
+    // -- Write the parts.
+    remove_bundle_parts(dst_file)?;
+    if report.num_files == 0 {
+        return Ok(report);
+    }
+
+    let split = parts.len() > 1;
+    let num_parts = parts.len();
+    for (idx, part) in parts.into_iter().enumerate() {
+        let mut content = String::new();
+        if format.toc {
+            let part_num = split.then_some((idx + 1, num_parts));
+            content.push_str(&toc_title(format, part_num));
+            content.push('\n');
+            for line in part.toc {
+                content.push_str(&line);
+                content.push('\n');
+            }
+        }
+        for section in part.sections {
+            content.push_str(&section);
+        }
+
+        let part_file = if split { part_path(dst_file, idx + 1) } else { dst_file.to_path_buf() };
+        fs::write(&part_file, &content)?;
+        report.size += content.len() as u64;
+        report.parts.push(part_file);
+    }
+
+    Ok(report)
+}
+
+struct Part {
+    toc: Vec<String>,
+    sections: Vec<String>,
+    len: usize,
+}
+
+impl Part {
+    /// `len` is what the part has before its sections (the table of contents title).
+    fn new(len: usize) -> Self {
+        Self {
+            toc: Vec::new(),
+            sections: Vec::new(),
+            len,
+        }
+    }
+}
+
+/// Commented title of the table of contents, `part_num` being `(num, of)` for a split bundle.
+fn toc_title(format: &BundleFormat, part_num: Option<(usize, usize)>) -> String {
+    let title = match part_num {
+        Some((num, of)) => format!("=== table of contents (part {num} of {of})"),
+        None => "=== table of contents".to_string(),
+    };
+    format.comment.comment(&title)
+}
+
+/// `file` relative to `base_dir` when under it, as listed otherwise.
+fn display_path(file: &Path, base_dir: Option<&Path>) -> String {
+    let rel = base_dir.and_then(|base| {
+        let base = base.canonicalize().ok()?;
+        let file = file.canonicalize().ok()?;
+        file.strip_prefix(base).ok().map(Path::to_path_buf)
+    });
+    rel.as_deref()
+        .unwrap_or(file)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn render_content(content: &str, line_numbers: bool) -> String {
+    let content = content.trim_end_matches('\n');
+    if !line_numbers {
+        return content.to_string();
+    }
+
+    let width = content.lines().count().to_string().len();
+    content
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| format!("{:>width$} | {line}", idx + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn human_size(size: u64) -> String {
+    if size < 1024 {
+        format!("{size} B")
+    } else {
+        format!("{:.1} KB", size as f64 / 1024.)
+    }
+}
+
+/// Removes `dst_file` and its `-partN` files, if any.
+fn remove_bundle_parts(dst_file: &Path) -> Result<()> {
+    if dst_file.is_file() {
+        fs::remove_file(dst_file)?;
+    }
+
+    let Some(dir) = dst_file.parent().filter(|d| d.is_dir()) else {
+        return Ok(());
+    };
+    let stem = dst_file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let part_prefix = format!("{stem}-part");
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path.x_file_name().starts_with(&part_prefix)
+            && path.x_extension() == dst_file.x_extension()
+        {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `dir/name.ext` -> `dir/name-part2.ext`
+pub fn part_path(dst_file: &Path, num: usize) -> PathBuf {
+    let stem = dst_file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let file_name = match dst_file.x_extension() {
+        "" => format!("{stem}-part{num}"),
+        ext => format!("{stem}-part{num}.{ext}"),
+    };
+    dst_file.with_file_name(file_name)
+}
+
+/// Returns `None` for binary files (NUL bytes or not UTF-8).
+fn read_text(file: &Path) -> Result<Option<String>> {
+    let bytes = fs::read(file)?;
+    if bytes.iter().take(BINARY_SNIFF_LEN).any(|b| *b == 0) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::files::ensure_dir;
+    use tempfile::TempDir;
+
+    const LIMITS: BundleLimits = BundleLimits {
+        max_bundle_size: 100,
+        max_file_size: 60,
+    };
+
+    fn write(dir: &TempDir, name: &str, content: &[u8]) -> Result<PathBuf> {
+        let path = dir.path().join(name);
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_bundle_split_at_file_boundaries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let files = vec![
+            write(&dir, "a.txt", &[b'a'; 40])?,
+            write(&dir, "b.txt", &[b'b'; 40])?,
+            write(&dir, "c.txt", &[b'c'; 40])?,
+        ];
+        let dst = dir.path().join("out.txt");
+
+        let report = bundle_to_files(files, &dst, LIMITS, &BundleFormat::default())?;
+
+        assert_eq!(report.num_files, 3);
+        assert_eq!(report.parts, [part_path(&dst, 1), part_path(&dst, 2), part_path(&dst, 3)]);
+        assert!(!dst.exists());
+        for part in report.parts.iter() {
+            assert!(fs::metadata(part)?.len() <= LIMITS.max_bundle_size);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundle_single_part_removes_old_parts() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dst = dir.path().join("out.txt");
+        fs::write(part_path(&dst, 1), "old")?;
+        fs::write(part_path(&dst, 2), "old")?;
+        let files = vec![write(&dir, "a.txt", b"hello")?];
+
+        let report = bundle_to_files(files, &dst, LIMITS, &BundleFormat::default())?;
+
+        assert_eq!(report.parts, [dst.as_path()]);
+        assert!(fs::read_to_string(&dst)?.contains("hello"));
+        assert!(!part_path(&dst, 1).exists());
+        assert!(!part_path(&dst, 2).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundle_skip_binary_and_too_large() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let files = vec![
+            write(&dir, "bin.dat", &[0, 1, 2])?,
+            write(&dir, "big.txt", &[b'x'; 61])?,
+            write(&dir, "ok.txt", b"ok")?,
+        ];
+        let dst = dir.path().join("out.txt");
+
+        let report = bundle_to_files(files, &dst, LIMITS, &BundleFormat::default())?;
+
+        assert_eq!(report.num_files, 1);
+        let skipped: Vec<(&str, &SkipReason)> =
+            report.skipped.iter().map(|s| (s.path.x_file_name(), &s.reason)).collect();
+        assert!(matches!(
+            skipped[..],
+            [
+                ("bin.dat", SkipReason::Binary),
+                ("big.txt", SkipReason::TooLarge { size: 61, max: 60 })
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundle_skip_too_large_once_rendered() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // Under the limits, but not with its header, line numbers and table of contents.
+        let files = vec![write(&dir, "a.txt", &[b'a'; 60])?, write(&dir, "b.txt", b"ok")?];
+        let dst = dir.path().join("out.txt");
+        let format = BundleFormat {
+            base_dir: Some(dir.path().to_path_buf()),
+            toc: true,
+            line_numbers: true,
+            ..BundleFormat::default()
+        };
+        let limits = BundleLimits {
+            max_bundle_size: 150,
+            max_file_size: 1000,
+        };
+
+        let report = bundle_to_files(files, &dst, limits, &format)?;
+
+        assert_eq!(report.num_files, 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].path.x_file_name(), "a.txt");
+        assert!(report.size <= limits.max_bundle_size);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundle_format() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        ensure_dir(&dir.path().join("docs"))?;
+        let files = vec![write(&dir, "docs/a.md", b"# Title\nline 2\n")?];
+        let dst = dir.path().join("out.md");
+        let format = BundleFormat {
+            comment: CommentStyle::for_ext("md"),
+            base_dir: Some(dir.path().to_path_buf()),
+            toc: true,
+            line_numbers: true,
+            file_meta: true,
+        };
+
+        let limits = BundleLimits {
+            max_bundle_size: 10_000,
+            max_file_size: 10_000,
+        };
+
+        let report = bundle_to_files(files, &dst, limits, &format)?;
+
+        let content = fs::read_to_string(&report.parts[0])?;
+        let toc = "<!-- === table of contents -->\n<!-- - docs/a.md (15 B) -->\n";
+        assert!(content.starts_with(toc), "{content}");
+        assert!(content.contains("<!-- === file path: docs/a.md | 15 B | modified "), "{content}");
+        assert!(content.contains("1 | # Title\n2 | line 2\n"), "{content}");
+        Ok(())
+    }
+}
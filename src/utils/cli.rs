@@ -1,20 +1,85 @@
 use console::{Style, style, StyledObject};
-use dialoguer::Input;
+use dialoguer::{Confirm, Input, Select};
 use dialoguer::theme::ColorfulTheme;
-use crate::Result;
+use indicatif::{ProgressBar, ProgressStyle};
+use crate::{Error, Result};
+use std::io;
 
 // Prompts the user for input and returns the input as a String
 pub fn prompt(text: &str) -> Result<String> {
-    let theme = ColorfulTheme {
+    let theme = theme();
+    let input = Input::with_theme(&theme);
+    let res = input.with_prompt(text).interact()?;
+
+    Ok(res)
+}
+
+// Whether `err` is a prompt interrupted with Ctrl-C
+pub fn is_interrupted(err: &Error) -> bool {
+    matches!(err.downcast_ref::<dialoguer::Error>(), Some(dialoguer::Error::IO(e)) if e.kind() == io::ErrorKind::Interrupted)
+}
+
+// Prompts the user for input, `default` being used on an empty input
+pub fn prompt_default(text: &str, default: &str) -> Result<String> {
+    let theme = theme();
+    let input = Input::with_theme(&theme);
+    let res = input.with_prompt(text).default(default.to_string()).interact_text()?;
+
+    Ok(res)
+}
+
+// Prompts the user for input, starting from `initial` to edit it
+pub fn prompt_edit(text: &str, initial: &str) -> Result<String> {
+    let theme = theme();
+    let input = Input::<String>::with_theme(&theme);
+    let res = input.with_prompt(text).with_initial_text(initial).interact_text()?;
+
+    Ok(res)
+}
+
+// Asks the user a yes/no question
+pub fn confirm(text: &str, default: bool) -> Result<bool> {
+    let res = Confirm::with_theme(&theme()).with_prompt(text).default(default).interact()?;
+
+    Ok(res)
+}
+
+// Asks the user to pick one of `items`, returns its index
+pub fn select(text: &str, items: &[&str], default: usize) -> Result<usize> {
+    let theme = theme();
+    let res = Select::with_theme(&theme).with_prompt(text).items(items).default(default).interact()?;
+
+    Ok(res)
+}
+
+fn theme() -> ColorfulTheme {
+    ColorfulTheme {
         prompt_style: Style::new().for_stderr().color256(45),
         prompt_prefix: style("?".to_string()).color256(45).for_stderr(),
         ..ColorfulTheme::default()
-    };
+    }
+}
 
-    let input = Input::with_theme(&theme);
-    let res = input.with_prompt(text).interact()?;
+// Progress bar of `total` bytes, with a `{msg}` for the file counts.
+pub fn new_progress_bar(total: u64) -> ProgressBar {
+    let style = ProgressStyle::with_template(
+        "{prefix} Uploading [{bar:30.yellow}] {bytes}/{total_bytes} {msg}",
+    )
+    .unwrap_or_else(|_| ProgressStyle::default_bar())
+    .progress_chars("=> ");
 
-    Ok(res)
+    let bar = ProgressBar::new(total)
+        .with_style(style)
+        .with_prefix(ico_uploading().to_string());
+    bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    bar
+}
+
+// Spinner with `msg`, until cleared.
+pub fn new_spinner(msg: &str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner().with_message(msg.to_string());
+    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+    spinner
 }
 
 // Icons
@@ -22,6 +87,10 @@ pub fn ico_res() -> StyledObject<&'static str> {
     style("➤").color256(45)
 }
 
+pub fn ico_user() -> StyledObject<&'static str> {
+    style("?").color256(45)
+}
+
 pub fn ico_check() -> StyledObject<&'static str> {
     style("✔").green()
 }
@@ -42,7 +111,23 @@ pub fn ico_err() -> StyledObject<&'static str> {
     style("✗").red()
 }
 
+pub fn ico_warn() -> StyledObject<&'static str> {
+    style("⚠").yellow()
+}
+
+pub fn ico_attach() -> StyledObject<&'static str> {
+    style("📎").color256(45)
+}
+
+pub fn ico_info() -> StyledObject<&'static str> {
+    style("ℹ").color256(45)
+}
+
 // Text Output
 pub fn txt_res(text: String) -> StyledObject<String> {
     style(text).bright()
-}
\ No newline at end of file
+}
+
+pub fn txt_dim(text: String) -> StyledObject<String> {
+    style(text).dim()
+}
@@ -1,35 +1,12 @@
 use crate::Result;
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
 use std::ffi::OsStr;
 use std::fs::{self, File};
-use std::io::{BufRead as _, BufReader, BufWriter, Write};
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-#[ allow(unused)]
-pub fn bundle_to_file( files: Vec<PathBuf>, dst_file: &Path) -> Result<()> {
-    let mut writer = BufWriter::new(File::create(dst_file)?);
-
-    for file in files {
-        if !file.is_file() {
-            return Err(format!("Cannot bundle '{:?}' is not a file.", file).into());
-        }
-
-        let reader = BufReader::new(File::open(&file)?);
-
-        writeln!(writer, "\n// === file path: {}\n", file.to_string_lossy())?;
-
-        for line in reader.lines() {
-            let line = line?;
-            writeln!(writer, "{}", line)?;
-        }
-        writeln!(writer, "\n\n")?;
-    }
-    writer.flush()?;
-
-    Ok(())
-}
-
 #[ allow(unused)]
 pub fn load_from_toml<T>(file: impl AsRef<Path>) -> Result<T>
 where
@@ -73,6 +50,18 @@ pub fn ensure_dir(dir: &Path) -> Result<bool> {
     }
 }
 
+/// Lists the files of `dir`, sorted by path.
+///
+/// - `include_globs`: a file is listed only if it matches one of them
+///   (`None` lists all files).
+/// - `exclude_globs`: files (and directories) matching one of them are left out,
+///   even if included.
+/// - Globs are matched against the path relative to `dir`, and `*` does not
+///   cross `/`: `*.md` only lists the top level files, `**/*.md` lists them at
+///   any depth, `docs/*.md` the ones directly under `docs/`.
+/// - `.gitignore` and `.ignore` files found in `dir` and its parents are
+///   honored (even outside of a git repository), and the
+///   `base_dir_exclude_globs` directories (`.git`, `target`) are never walked.
 #[ allow(unused)]
 pub fn list_files(
     dir: &Path,
@@ -81,39 +70,62 @@ pub fn list_files(
 ) -> Result<Vec<PathBuf>> {
     let base_dir_exclude = base_dir_exclude_globs()?;
 
-    // Determine recursive depth
-    let depth = include_globs
-        .map(|globs| globs.iter().any(|&g| g.contains("**")))
-        .map(|v| if v { 100 } else { 1  })
-        .unwrap_or(1);
+    // Determine recursive depth (no need to go deeper than the globs can match)
+    let depth = include_globs.and_then(|globs| {
+        globs
+            .iter()
+            .map(|g| glob_depth(g))
+            .collect::<Option<Vec<usize>>>()
+            .and_then(|depths| depths.into_iter().max())
+    });
 
     // Prep globs
     let include_globs = include_globs.map(get_glob_set).transpose()?;
     let exclude_globs = exclude_globs.map(get_glob_set).transpose()?;
 
     // Build file iterator
-    let walk_dir_it =
-        WalkDir::new(dir)
-            .max_depth(depth)
-            .into_iter()
-            .filter_entry(|e|
-                if e.file_type().is_dir() {
-                    !base_dir_exclude.is_match(e.path())
-                }
-                // else file, we apply the globs
-                else {
-                    if let Some(exclude_globs) = exclude_globs.as_ref() {
-                        //     otherwise, evaluate the include
-                        match include_globs.as_ref() {
-                            Some(globs) => globs.is_match(e.path()),
-                            None => true,
-                        }
-                    } else {
-                        true
-                    }
+    let root = dir.to_path_buf();
+    let walk_dir_it = WalkBuilder::new(dir)
+        .max_depth(depth)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .standard_filters(false)
+        .git_ignore(true)
+        .git_exclude(true)
+        .ignore(true)
+        .parents(true)
+        .require_git(false)
+        .filter_entry(move |e| {
+            // The root itself is never filtered.
+            let Ok(rel_path) = e.path().strip_prefix(&root) else {
+                return true;
+            };
+            if rel_path.as_os_str().is_empty() {
+                return true;
+            }
+
+            let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if is_dir && base_dir_exclude.is_match(e.file_name()) {
+                return false;
+            }
+
+            if let Some(exclude_globs) = exclude_globs.as_ref() {
+                if exclude_globs.is_match(rel_path) {
+                    return false;
                 }
-            )
-            .filter_map(|e| e.ok().filter(|e| e.file_type().is_file()));
+            }
+
+            // Includes only apply to files, directories are walked.
+            is_dir
+                || include_globs
+                    .as_ref()
+                    .map(|globs| globs.is_match(rel_path))
+                    .unwrap_or(true)
+        })
+        .build()
+        .filter_map(|e| {
+            e.ok()
+                .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        });
 
     let paths = walk_dir_it.map(|e| e.into_path());
 
@@ -124,10 +136,69 @@ pub fn base_dir_exclude_globs() -> Result<GlobSet> {
     get_glob_set(&[".git", "target"])
 }
 
+/// True if any component of `path` matches `base_dir_exclude_globs`.
+pub fn is_base_dir_excluded(path: &Path) -> Result<bool> {
+    let base_dir_exclude = base_dir_exclude_globs()?;
+    Ok(path
+        .components()
+        .any(|c| base_dir_exclude.is_match(c.as_os_str())))
+}
+
+const GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+/// Whether `pattern` is a glob rather than a plain path.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(GLOB_CHARS)
+}
+
+/// Expands a file path or a glob (e.g. `src/**/*.rs`, relative to the current dir)
+/// into the matching files, skipping anything excluded by `base_dir_exclude_globs`.
+/// In globs, `*` does not cross `/` (use `**` for that).
+pub fn expand_path_or_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    // -- Plain path
+    if !is_glob(pattern) {
+        let path = PathBuf::from(pattern);
+        if is_base_dir_excluded(&path)? {
+            return Err(format!("'{pattern}' is excluded (.git, target)").into());
+        }
+        if !path.is_file() {
+            return Err(format!("File not found: {pattern}").into());
+        }
+        return Ok(vec![path]);
+    }
+
+    // -- Glob, walked from its literal base dir (e.g. `src/ais` for `src/ais/*.rs`)
+    let base: PathBuf = Path::new(pattern)
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(GLOB_CHARS))
+        .collect();
+    let walk_root = if base.as_os_str().is_empty() { Path::new(".") } else { &base };
+
+    let glob = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher();
+    let base_dir_exclude = base_dir_exclude_globs()?;
+
+    let files = WalkDir::new(walk_root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !base_dir_exclude.is_match(e.file_name()))
+        .filter_map(|e| e.ok().filter(|e| e.file_type().is_file()))
+        .map(|e| e.into_path())
+        // `./a.rs` when walking `.`, so that `*.rs` matches.
+        .map(|p| p.strip_prefix(".").map(Path::to_path_buf).unwrap_or(p))
+        .filter(|p| glob.is_match(p))
+        .collect();
+
+    Ok(files)
+}
+
+/// Builds a glob set where `*` does not match `/` (use `**` for that).
 pub fn get_glob_set(globs:&[&str]) -> Result<GlobSet> {
     let mut builder = GlobSetBuilder::new();
     for glob in globs {
-        builder.add(Glob::new(glob)?);
+        builder.add(GlobBuilder::new(glob).literal_separator(true).build()?);
     }
     Ok(builder.build()?)
 }
@@ -165,4 +236,189 @@ impl XFile for Path {
     fn x_extension(&self) -> &str {
         self.extension().and_then(OsStr::to_str).unwrap_or("")
     }
-}
\ No newline at end of file
+}
+
+/// Path components a glob can match, `None` for any (`**`).
+/// For brace alternatives, the deepest one (e.g. 3 for `src/{*.rs,deep/*.md}`).
+fn glob_depth(glob: &str) -> Option<usize> {
+    if glob.contains("**") {
+        return None;
+    }
+    expand_braces(glob).iter().map(|g| g.split('/').count()).max()
+}
+
+/// The globs of the `{a,b}` alternatives of `glob` (nested ones included).
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(start) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+
+    // -- Matching `}`, and the top level `,` in between
+    let mut level = 0;
+    let mut commas = Vec::new();
+    let mut end = None;
+    for (idx, c) in glob[start..].char_indices().map(|(i, c)| (start + i, c)) {
+        match c {
+            '{' => level += 1,
+            '}' => {
+                level -= 1;
+                if level == 0 {
+                    end = Some(idx);
+                    break;
+                }
+            }
+            ',' if level == 1 => commas.push(idx),
+            _ => (),
+        }
+    }
+    let Some(end) = end else {
+        return vec![glob.to_string()];
+    };
+
+    let bounds: Vec<usize> = std::iter::once(start).chain(commas).chain(std::iter::once(end)).collect();
+    bounds
+        .windows(2)
+        .flat_map(|w| expand_braces(&format!("{}{}{}", &glob[..start], &glob[w[0] + 1..w[1]], &glob[end + 1..])))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Fixture tree:
+    /// ```text
+    /// .gitignore          ("ignored/" and "*.log")
+    /// a.rs
+    /// b.md
+    /// debug.log           (gitignored)
+    /// ignored/x.rs        (gitignored)
+    /// src/lib.rs
+    /// src/deep/mod.rs
+    /// src/deep/notes.md
+    /// vendor/v.rs
+    /// target/t.rs         (base dir exclude)
+    /// .git/hook.rs        (base dir exclude)
+    /// ```
+    fn fixture() -> Result<TempDir> {
+        let root = tempfile::tempdir()?;
+        let files = [
+            (".gitignore", "ignored/\n*.log\n"),
+            ("a.rs", ""),
+            ("b.md", ""),
+            ("debug.log", ""),
+            ("ignored/x.rs", ""),
+            ("src/lib.rs", ""),
+            ("src/deep/mod.rs", ""),
+            ("src/deep/notes.md", ""),
+            ("vendor/v.rs", ""),
+            ("target/t.rs", ""),
+            (".git/hook.rs", ""),
+        ];
+        for (path, content) in files {
+            let path = root.path().join(path);
+            ensure_dir(path.parent().unwrap())?;
+            fs::write(path, content)?;
+        }
+        Ok(root)
+    }
+
+    fn list_rel(
+        root: &TempDir,
+        include_globs: Option<&[&str]>,
+        exclude_globs: Option<&[&str]>,
+    ) -> Result<Vec<String>> {
+        let files = list_files(root.path(), include_globs, exclude_globs)?;
+        Ok(files
+            .iter()
+            .map(|f| f.strip_prefix(root.path()).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect())
+    }
+
+    #[test]
+    fn test_list_files_no_globs() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, None, None)?;
+
+        assert_eq!(
+            files,
+            [".gitignore", "a.rs", "b.md", "src/deep/mod.rs", "src/deep/notes.md", "src/lib.rs", "vendor/v.rs"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_include_top_level_only() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, Some(&["*.rs"]), None)?;
+
+        assert_eq!(files, ["a.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_include_any_depth() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, Some(&["**/*.rs"]), None)?;
+
+        assert_eq!(files, ["a.rs", "src/deep/mod.rs", "src/lib.rs", "vendor/v.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_include_relative_to_dir() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, Some(&["src/*.rs", "src/deep/*.md"]), None)?;
+
+        assert_eq!(files, ["src/deep/notes.md", "src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_include_brace_alternatives() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, Some(&["src/{*.rs,deep/*.md}"]), None)?;
+
+        assert_eq!(files, ["src/deep/notes.md", "src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_exclude_without_include() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, None, Some(&["vendor", "*.md", ".*"]))?;
+
+        assert_eq!(files, ["a.rs", "src/deep/mod.rs", "src/deep/notes.md", "src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_include_and_exclude() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, Some(&["**/*.rs", "**/*.md"]), Some(&["src/deep/**", "vendor/**"]))?;
+
+        assert_eq!(files, ["a.rs", "b.md", "src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_gitignore_and_base_dir_exclude() -> Result<()> {
+        let root = fixture()?;
+
+        let files = list_rel(&root, Some(&["**/*.rs", "**/*.log"]), None)?;
+
+        assert!(!files.iter().any(|f| f.starts_with("ignored/")), "{files:?}");
+        assert!(!files.iter().any(|f| f.ends_with(".log")), "{files:?}");
+        assert!(!files.iter().any(|f| f.starts_with("target/")), "{files:?}");
+        assert!(!files.iter().any(|f| f.starts_with(".git/")), "{files:?}");
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use crate::utils::cli::txt_dim;
+use std::future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::watch;
+
+/// Count of the Ctrl-C received, for `RunInterrupts` to wait on.
+static INTERRUPTS: OnceLock<watch::Sender<u64>> = OnceLock::new();
+/// Whether a run is in flight (a `RunInterrupts` is alive).
+static IN_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Listens to Ctrl-C for the rest of the process, which is no longer killed by it:
+/// during a run, it is passed on to `RunInterrupts` (to cancel the run),
+/// otherwise it is only recorded, and the command goes on.
+/// At the prompt, the terminal is in raw mode: Ctrl-C is read as a key, and
+/// the prompt fails with `is_interrupted`, for the session to end normally.
+pub fn listen_ctrl_c() {
+    if INTERRUPTS.get().is_some() {
+        return;
+    }
+    let interrupts = INTERRUPTS.get_or_init(|| watch::channel(0).0);
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            interrupts.send_modify(|count| *count += 1);
+            if !IN_RUN.load(Ordering::SeqCst) {
+                let note = "Ctrl-C ignored while a command runs (Ctrl-C at the prompt quits)";
+                println!("\n{}", txt_dim(note.to_string()));
+            }
+        }
+    });
+}
+
+/// The Ctrl-C received while a run is in flight, until dropped.
+pub struct RunInterrupts {
+    rx: Option<watch::Receiver<u64>>,
+}
+
+impl RunInterrupts {
+    /// Only the Ctrl-C received from now on are passed on.
+    pub fn start() -> Self {
+        IN_RUN.store(true, Ordering::SeqCst);
+        Self {
+            rx: INTERRUPTS.get().map(|tx| tx.subscribe()),
+        }
+    }
+
+    /// Waits for the next Ctrl-C (forever without `listen_ctrl_c`, as it then kills the process).
+    pub async fn next(&mut self) {
+        if let Some(rx) = self.rx.as_mut() {
+            if rx.changed().await.is_ok() {
+                return;
+            }
+        }
+        future::pending::<()>().await
+    }
+}
+
+impl Drop for RunInterrupts {
+    fn drop(&mut self) {
+        IN_RUN.store(false, Ordering::SeqCst);
+    }
+}
@@ -1,2 +1,5 @@
+pub mod bundle;
 pub mod cli;
-pub mod files;
\ No newline at end of file
+pub mod files;
+pub mod interrupt;
+pub mod template;
\ No newline at end of file
@@ -0,0 +1,180 @@
+use crate::Result;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Renders the template `file`:
+/// - `{{include path/to/file.md}}` inserts the rendered file (relative to the including file).
+/// - `{{env.NAME}}` inserts an environment variable.
+/// - `{{some.name}}` inserts the value `resolve` returns for it (`None` if unknown).
+/// - `{{name | default text}}` falls back to `default text` when the variable is not set.
+/// - `\{{` is a literal `{{`.
+///
+/// Unknown variables and an unclosed `{{` are kept as written, with a warning,
+/// as the file may contain `{{` for other reasons (Handlebars, Jinja, Rust format examples, ...).
+pub fn render_file(file: &Path, resolve: &dyn Fn(&str) -> Result<Option<String>>) -> Result<Rendered> {
+    let mut stack = Vec::new();
+    let mut rendered = Rendered::default();
+    rendered.text = render_file_inner(file, resolve, &mut stack, &mut rendered)?;
+    Ok(rendered)
+}
+
+#[derive(Debug, Default)]
+pub struct Rendered {
+    pub text: String,
+    /// The files read (canonicalized), the rendered file first and then its includes.
+    pub files: Vec<PathBuf>,
+    /// E.g. `inst.md:3: unknown variable 'name', kept as is`.
+    pub warnings: Vec<String>,
+}
+
+fn render_file_inner(
+    file: &Path,
+    resolve: &dyn Fn(&str) -> Result<Option<String>>,
+    stack: &mut Vec<PathBuf>,
+    rendered: &mut Rendered,
+) -> Result<String> {
+    let canonical = file
+        .canonicalize()
+        .map_err(|e| format!("Cannot read '{}': {e}", file.display()))?;
+    if stack.contains(&canonical) {
+        let cycle: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|f| f.display().to_string())
+            .collect();
+        return Err(format!("Include cycle: {}", cycle.join(" -> ")).into());
+    }
+    if stack.len() >= MAX_INCLUDE_DEPTH {
+        return Err(format!("Includes nested deeper than {MAX_INCLUDE_DEPTH} at '{}'", file.display()).into());
+    }
+
+    let content = fs::read_to_string(&canonical)
+        .map_err(|e| format!("Cannot read '{}': {e}", file.display()))?;
+
+    if !rendered.files.contains(&canonical) {
+        rendered.files.push(canonical.clone());
+    }
+    stack.push(canonical);
+    let res = render_str(&content, file, resolve, stack, rendered);
+    stack.pop();
+
+    res
+}
+
+fn render_str(
+    content: &str,
+    file: &Path,
+    resolve: &dyn Fn(&str) -> Result<Option<String>>,
+    stack: &mut Vec<PathBuf>,
+    rendered: &mut Rendered,
+) -> Result<String> {
+    let base_dir = file.parent().unwrap_or(Path::new(""));
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find("{{") {
+        // -- Escaped `\{{`
+        if rest[..start].ends_with('\\') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        out.push_str(&rest[..start]);
+        let line = content.len() - rest.len() + start;
+        let line = content[..line].matches('\n').count() + 1;
+        let err_at = |msg: String| format!("{}:{line}: {msg}", file.display());
+
+        let Some(len) = rest[start..].find("}}") else {
+            rendered.warnings.push(err_at("unclosed '{{', kept as is".to_string()));
+            out.push_str(&rest[start..]);
+            return Ok(out);
+        };
+        let raw = &rest[start..start + len + 2];
+        let expr = rest[start + 2..start + len].trim();
+        rest = &rest[start + len + 2..];
+
+        // -- Include
+        if let Some(path) = expr.strip_prefix("include ") {
+            let rendered = render_file_inner(&base_dir.join(path.trim()), resolve, stack, rendered)
+                .map_err(|e| err_at(e.to_string()))?;
+            out.push_str(rendered.trim_end_matches('\n'));
+            continue;
+        }
+
+        // -- Variable
+        let (name, default) = match expr.split_once('|') {
+            Some((name, default)) => (name.trim(), Some(default.trim())),
+            None => (expr, None),
+        };
+        let value = match name.strip_prefix("env.") {
+            Some(var) => env::var(var).ok(),
+            None => resolve(name).map_err(|e| err_at(e.to_string()))?,
+        };
+        match (value, default) {
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) if name.starts_with("env.") => {
+                return Err(err_at(format!("environment variable '{}' is not set", &name[4..])).into())
+            }
+            (None, None) => {
+                rendered.warnings.push(err_at(format!("unknown variable '{name}', kept as is")));
+                out.push_str(raw);
+            }
+        }
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(name: &str) -> Result<Option<String>> {
+        Ok((name == "buddy.name").then(|| "buddy-01".to_string()))
+    }
+
+    #[test]
+    fn test_render_vars_includes_and_escape() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("common"))?;
+        fs::write(dir.path().join("common/preamble.md"), "Be {{ tone | concise }}.\n")?;
+        fs::write(
+            dir.path().join("inst.md"),
+            "{{include common/preamble.md}}\nI am {{buddy.name}}. \\{{not a var}}",
+        )?;
+
+        let rendered = render_file(&dir.path().join("inst.md"), &resolve)?;
+
+        assert_eq!(rendered.text, "Be concise.\nI am buddy-01. {{not a var}}");
+        let files: Vec<&str> = rendered.files.iter().filter_map(|f| f.file_name()?.to_str()).collect();
+        assert_eq!(files, ["inst.md", "preamble.md"]);
+        assert!(rendered.warnings.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_errors() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("a.md"), "ok\n{{buddy.nope}} {{#if x}}\n{{ unclosed")?;
+        fs::write(dir.path().join("b.md"), "{{include c.md}}")?;
+        fs::write(dir.path().join("c.md"), "{{include b.md}}")?;
+
+        // -- Unknown variables and unclosed `{{` are kept, with warnings
+        let rendered = render_file(&dir.path().join("a.md"), &resolve)?;
+        assert_eq!(rendered.text, "ok\n{{buddy.nope}} {{#if x}}\n{{ unclosed");
+        assert_eq!(rendered.warnings.len(), 3);
+        assert!(rendered.warnings[0].ends_with("a.md:2: unknown variable 'buddy.nope', kept as is"));
+        assert!(rendered.warnings[2].ends_with("a.md:3: unclosed '{{', kept as is"));
+
+        let err = render_file(&dir.path().join("b.md"), &resolve).unwrap_err().to_string();
+        assert!(err.contains("Include cycle"), "{err}");
+        Ok(())
+    }
+}